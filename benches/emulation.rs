@@ -0,0 +1,81 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use macroquad::texture::Image;
+use wasm_chip8::display::{fb_to_img, preset, Persistence, Phosphor};
+use wasm_chip8::headless::Headless;
+use wasm_chip8::roms::get_bytes;
+
+const STEPS: u64 = 10_000;
+
+// Draws a 15 row sprite over and over while moving it diagonally, wrapping at the edges.
+const DRAW_LOOP: [u8; 14] = [
+    0x60, 0x00, // LD V0, 0x00
+    0x61, 0x00, // LD V1, 0x00
+    0xA0, 0x00, // LD I, 0x000
+    0xD0, 0x1F, // DRW V0, V1, 0xF
+    0x70, 0x03, // ADD V0, 0x03
+    0x71, 0x01, // ADD V1, 0x01
+    0x12, 0x06, // JP 0x206
+];
+
+fn run_steps(emu: &mut Headless) {
+    for _ in 0..STEPS {
+        emu.cpu.step().unwrap();
+    }
+}
+
+fn step(c: &mut Criterion) {
+    let mut group = c.benchmark_group("step");
+    group.throughput(Throughput::Elements(STEPS));
+
+    for rom in ["BRIX", "INVADERS", "KALEID"] {
+        let bytes = get_bytes(rom);
+        group.bench_function(rom, |b| {
+            b.iter_batched_ref(
+                || Headless::new(&bytes).unwrap(),
+                run_steps,
+                BatchSize::SmallInput,
+            )
+        });
+    }
+
+    group.bench_function("draw_xyn", |b| {
+        b.iter_batched_ref(
+            || Headless::new(&DRAW_LOOP).unwrap(),
+            run_steps,
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.finish();
+}
+
+fn frame(c: &mut Criterion) {
+    let mut emu = Headless::new(&get_bytes("INVADERS")).unwrap();
+    emu.run_frames(600).unwrap();
+
+    c.bench_function("run_frame", |b| b.iter(|| emu.run_frame().unwrap()));
+}
+
+fn display(c: &mut Criterion) {
+    let mut emu = Headless::new(&get_bytes("KALEID")).unwrap();
+    emu.run_frames(60).unwrap();
+
+    let mut img = Image::gen_image_color(64, 32, macroquad::color::BLACK);
+    let palette = preset("Classic").unwrap();
+    let mut phosphor = Phosphor::new(Persistence::Decay, 0.5);
+    c.bench_function("fb_to_img", |b| {
+        b.iter(|| {
+            fb_to_img(
+                &mut img,
+                black_box(emu.cpu.get_framebuffer()),
+                emu.scheduler.previous_frame(),
+                &mut phosphor,
+                &palette,
+                1,
+            )
+        })
+    });
+}
+
+criterion_group!(benches, step, frame, display);
+criterion_main!(benches);
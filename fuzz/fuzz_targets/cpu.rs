@@ -0,0 +1,42 @@
+#![no_main]
+use libfuzzer_sys::arbitrary::{self, Arbitrary};
+use libfuzzer_sys::fuzz_target;
+use wasm_chip8::cpu::STACK_SIZE;
+use wasm_chip8::headless::Headless;
+
+const MAX_FRAMES: usize = 600;
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    st_compat: bool,
+    sh_compat: bool,
+    vip_timing: bool,
+    display_wait: bool,
+    /// Pressed keys as a bitmask, one entry per frame
+    keys: Vec<u16>,
+    /// Copied over the whole address space, including the font
+    mem: Vec<u8>,
+}
+
+fuzz_target!(|input: Input| {
+    let mut emu = Headless::new(&[]).unwrap();
+    emu.cpu.st_compat = input.st_compat;
+    emu.cpu.sh_compat = input.sh_compat;
+    emu.cpu.vip_timing = input.vip_timing;
+    emu.cpu.display_wait = input.display_wait;
+
+    let len = input.mem.len().min(emu.cpu.mem.len());
+    emu.cpu.mem[..len].copy_from_slice(&input.mem[..len]);
+
+    for keys in input.keys.iter().take(MAX_FRAMES) {
+        for key in 0..0x10 {
+            emu.cpu.set_key(key, keys & (1 << key) != 0);
+        }
+
+        // Errors are fine, panics are not
+        if emu.run_frame().is_err() {
+            break;
+        }
+        assert!(emu.cpu.stack.len() <= STACK_SIZE);
+    }
+});
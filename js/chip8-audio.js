@@ -0,0 +1,51 @@
+// Plays the samples streamed from src/sound.rs through WebAudio, queueing each chunk
+// right after the previous one.
+var chip8_audio = {
+    memory: null,
+    ctx: null,
+    next: 0
+};
+
+chip8_audio_register_plugin = function (importObject) {
+    importObject.env.chip8_audio_init = function () {
+        try {
+            chip8_audio.ctx = new (window.AudioContext || window.webkitAudioContext)();
+            return chip8_audio.ctx.sampleRate;
+        } catch (e) {
+            return 0;
+        }
+    }
+    importObject.env.chip8_audio_push = function (ptr, len, max_queued) {
+        var ctx = chip8_audio.ctx;
+        // Browsers keep the context suspended until the page has been interacted with
+        if (ctx.state == "suspended") {
+            ctx.resume();
+        }
+
+        var now = ctx.currentTime;
+        if (chip8_audio.next < now) {
+            // Fell behind, leave a little headroom so the next chunks connect seamlessly
+            chip8_audio.next = now + 0.05;
+        } else if (chip8_audio.next > now + max_queued) {
+            return;
+        }
+
+        var samples = new Float32Array(chip8_audio.memory.buffer, ptr, len);
+        var buffer = ctx.createBuffer(1, len, ctx.sampleRate);
+        buffer.getChannelData(0).set(samples);
+
+        var source = ctx.createBufferSource();
+        source.buffer = buffer;
+        source.connect(ctx.destination);
+        source.start(chip8_audio.next);
+        chip8_audio.next += buffer.duration;
+    }
+}
+
+miniquad_add_plugin({
+    register_plugin: chip8_audio_register_plugin,
+    on_init: function (wasm_memory, _wasm_exports) {
+        chip8_audio.memory = wasm_memory;
+    },
+    name: "chip8_audio"
+});
@@ -0,0 +1,28 @@
+// Offers files exported by src/export.rs as downloads.
+var chip8_download = {
+    memory: null
+};
+
+chip8_download_register_plugin = function (importObject) {
+    importObject.env.chip8_download = function (name_ptr, name_len, data_ptr, data_len) {
+        var name = new TextDecoder().decode(new Uint8Array(chip8_download.memory.buffer, name_ptr, name_len));
+        // Copy the data out, wasm memory may move before the download starts
+        var data = new Uint8Array(chip8_download.memory.buffer, data_ptr, data_len).slice();
+
+        var link = document.createElement("a");
+        link.href = URL.createObjectURL(new Blob([data]));
+        link.download = name;
+        link.click();
+        setTimeout(function () {
+            URL.revokeObjectURL(link.href);
+        }, 0);
+    }
+}
+
+miniquad_add_plugin({
+    register_plugin: chip8_download_register_plugin,
+    on_init: function (wasm_memory, _wasm_exports) {
+        chip8_download.memory = wasm_memory;
+    },
+    name: "chip8_download"
+});
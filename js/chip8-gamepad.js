@@ -0,0 +1,47 @@
+// Polls gamepads with the standard layout for src/gamepad.rs.
+// Standard button indices, in the order of keymap::Button::ALL
+var chip8_gamepad_layout = [12, 13, 14, 15, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9];
+
+function chip8_gamepads() {
+    if (!navigator.getGamepads) {
+        return [];
+    }
+    // Disconnected slots are null
+    return Array.prototype.filter.call(navigator.getGamepads(), function (pad) {
+        return pad && pad.connected;
+    });
+}
+
+chip8_gamepad_register_plugin = function (importObject) {
+    importObject.env.chip8_gamepad_count = function () {
+        return chip8_gamepads().length;
+    }
+    importObject.env.chip8_gamepad_buttons = function () {
+        var bits = 0;
+        chip8_gamepads().forEach(function (pad) {
+            chip8_gamepad_layout.forEach(function (index, bit) {
+                if (pad.buttons[index] && pad.buttons[index].pressed) {
+                    bits |= 1 << bit;
+                }
+            });
+        });
+        return bits;
+    }
+    // The axis pushed furthest across all gamepads
+    importObject.env.chip8_gamepad_axis = function (axis) {
+        var value = 0;
+        chip8_gamepads().forEach(function (pad) {
+            var v = pad.axes[axis] || 0;
+            if (Math.abs(v) > Math.abs(value)) {
+                value = v;
+            }
+        });
+        return value;
+    }
+}
+
+miniquad_add_plugin({
+    register_plugin: chip8_gamepad_register_plugin,
+    on_init: function (_wasm_memory, _wasm_exports) {},
+    name: "chip8_gamepad"
+});
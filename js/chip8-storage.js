@@ -0,0 +1,52 @@
+// Keeps the settings from src/settings.rs in localStorage.
+var chip8_storage = {
+    memory: null
+};
+
+function chip8_storage_string(ptr, len) {
+    return new TextDecoder().decode(new Uint8Array(chip8_storage.memory.buffer, ptr, len));
+}
+
+function chip8_storage_item(key_ptr, key_len) {
+    try {
+        return localStorage.getItem(chip8_storage_string(key_ptr, key_len));
+    } catch (e) {
+        // Storage can be disabled altogether
+        return null;
+    }
+}
+
+chip8_storage_register_plugin = function (importObject) {
+    // Length of the saved value in bytes, or -1 if there is none
+    importObject.env.chip8_storage_len = function (key_ptr, key_len) {
+        var item = chip8_storage_item(key_ptr, key_len);
+        return item === null ? -1 : new TextEncoder().encode(item).length;
+    }
+
+    importObject.env.chip8_storage_get = function (key_ptr, key_len, buf_ptr, buf_len) {
+        var item = chip8_storage_item(key_ptr, key_len);
+        var bytes = new TextEncoder().encode(item === null ? "" : item);
+        new Uint8Array(chip8_storage.memory.buffer, buf_ptr, buf_len).set(bytes.subarray(0, buf_len));
+    }
+
+    // 1 if saved, 0 if storage is disabled or full
+    importObject.env.chip8_storage_set = function (key_ptr, key_len, value_ptr, value_len) {
+        try {
+            localStorage.setItem(
+                chip8_storage_string(key_ptr, key_len),
+                chip8_storage_string(value_ptr, value_len)
+            );
+            return 1;
+        } catch (e) {
+            return 0;
+        }
+    }
+}
+
+miniquad_add_plugin({
+    register_plugin: chip8_storage_register_plugin,
+    on_init: function (wasm_memory, _wasm_exports) {
+        chip8_storage.memory = wasm_memory;
+    },
+    name: "chip8_storage"
+});
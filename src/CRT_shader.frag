@@ -3,11 +3,17 @@ varying vec4 color;
 varying vec2 uv;
 
 uniform sampler2D Texture;
+uniform float Curvature;
+uniform float Vignette;
+uniform float Scanlines;
+uniform float Grille;
+uniform float Brightness;
+
 // https://www.shadertoy.com/view/XtlSD7
 vec2 CRTCurveUV(vec2 uv)
 {
     uv = uv * 2.0 - 1.0;
-    vec2 offset = abs( uv.yx ) / vec2( 6.0, 4.0 );
+    vec2 offset = abs( uv.yx ) / vec2( 6.0, 4.0 ) * Curvature;
     uv = uv + uv * offset * offset;
     uv = uv * 0.5 + 0.5;
     return uv;
@@ -17,14 +23,14 @@ void DrawVignette( inout vec3 color, vec2 uv )
 {
     float vignette = uv.x * uv.y * ( 1.0 - uv.x ) * ( 1.0 - uv.y );
     vignette = clamp( pow( 16.0 * vignette, 0.3 ), 0.0, 1.0 );
-    color *= vignette;
+    color *= mix( 1.0, vignette, Vignette );
 }
 void DrawScanline( inout vec3 color, vec2 uv )
 {
     float iTime = 0.1;
-    float scanline 	= clamp( 0.95 + 0.05 * cos( 3.14 * ( uv.y + 0.008 * iTime ) * 240.0 * 1.0 ), 0.0, 1.0 );
-    float grille 	= 0.85 + 0.15 * clamp( 1.5 * cos( 3.14 * uv.x * 640.0 * 1.0 ), 0.0, 1.0 );
-    color *= scanline * grille * 1.2;
+    float scanline 	= clamp( 1.0 - Scanlines + Scanlines * cos( 3.14 * ( uv.y + 0.008 * iTime ) * 240.0 * 1.0 ), 0.0, 1.0 );
+    float grille 	= 1.0 - Grille + Grille * clamp( 1.5 * cos( 3.14 * uv.x * 640.0 * 1.0 ), 0.0, 1.0 );
+    color *= scanline * grille * Brightness;
 }
 void main() {
 
@@ -0,0 +1,19 @@
+precision lowp float;
+varying vec4 color;
+varying vec2 uv;
+
+uniform sampler2D Texture;
+uniform vec2 SourceSize;
+uniform float GridWidth;
+uniform float GridStrength;
+
+// Darkens the gaps between emulated pixels, like the cells of an LCD
+void main() {
+    vec3 res = texture2D(Texture, uv).rgb * color.rgb;
+
+    vec2 cell = fract(uv * SourceSize);
+    if (cell.x < GridWidth || cell.y < GridWidth) {
+        res *= 1.0 - GridStrength;
+    }
+    gl_FragColor = vec4(res, 1.0);
+}
@@ -0,0 +1,138 @@
+use crate::cpu::Cpu;
+use crate::scheduler::TIMER_HZ;
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_SAMPLE_RATE: u32 = 44100;
+
+// How long the volume takes to fade in or out, so the beep starts and stops without clicking
+const RAMP_SECONDS: f32 = 0.005;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Waveform {
+    Square,
+    Sine,
+    Triangle,
+}
+
+impl Waveform {
+    pub const ALL: [Waveform; 3] = [Waveform::Square, Waveform::Sine, Waveform::Triangle];
+
+    /// Value of the wave at `phase`, which runs from 0 to 1 over one period.
+    fn sample(self, phase: f32) -> f32 {
+        match self {
+            Waveform::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Sine => (phase * std::f32::consts::TAU).sin(),
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+        }
+    }
+}
+
+/// Bits per second the XO-CHIP audio pattern is played back at for a given pitch register.
+pub fn pattern_rate(pitch: u8) -> f32 {
+    4000.0 * 2f32.powf((f32::from(pitch) - 64.0) / 48.0)
+}
+
+/// Synthesizes the tone that plays while the sound timer is running, one emulated frame at a time.
+/// XO-CHIP ROMs that load an audio pattern hear that pattern instead of the configured waveform.
+pub struct Beeper {
+    pub sample_rate: u32,
+    pub waveform: Waveform,
+    pub frequency: f32,
+    pub volume: f32,
+    pub muted: bool,
+    phase: f32,
+    // Position in the XO-CHIP audio pattern, in bits
+    pattern_pos: f32,
+    gain: f32,
+    // Leftover samples when the sample rate isn't a multiple of the frame rate
+    remainder: u32,
+}
+
+impl Beeper {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            waveform: Waveform::Square,
+            frequency: 440.0,
+            volume: 0.25,
+            muted: false,
+            phase: 0.0,
+            pattern_pos: 0.0,
+            gain: 0.0,
+            remainder: 0,
+        }
+    }
+
+    /// Appends one frame worth of samples for the current state of `cpu` to `out`.
+    pub fn render_frame(&mut self, cpu: &Cpu, out: &mut Vec<f32>) {
+        let total = self.remainder + self.sample_rate;
+        let count = total / TIMER_HZ;
+        self.remainder = total % TIMER_HZ;
+
+        let target = if cpu.reg_sound > 0 && !self.muted {
+            self.volume
+        } else {
+            0.0
+        };
+        let ramp = 1.0 / (RAMP_SECONDS * self.sample_rate as f32);
+        let step = self.frequency / self.sample_rate as f32;
+        let pattern_step = pattern_rate(cpu.pitch) / self.sample_rate as f32;
+
+        for _ in 0..count {
+            if self.gain < target {
+                self.gain = (self.gain + ramp).min(target);
+            } else {
+                self.gain = (self.gain - ramp).max(target);
+            }
+
+            match &cpu.audio_pattern {
+                Some(pattern) => {
+                    let bit = self.pattern_pos as usize;
+                    let level = if pattern[bit / 8] & (0x80 >> (bit % 8)) != 0 {
+                        1.0
+                    } else {
+                        -1.0
+                    };
+                    out.push(level * self.gain);
+                    self.pattern_pos = (self.pattern_pos + pattern_step) % 128.0;
+                }
+                None => {
+                    out.push(self.waveform.sample(self.phase) * self.gain);
+                    self.phase = (self.phase + step).fract();
+                }
+            }
+        }
+    }
+}
+
+/// Encodes mono samples as a 16-bit PCM WAV file.
+pub fn encode_wav(samples: &[f32], sample_rate: u32) -> Vec<u8> {
+    let data_len = samples.len() as u32 * 2;
+
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&1u16.to_le_bytes()); // Mono
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate * 2).to_le_bytes()); // Bytes per second
+    wav.extend_from_slice(&2u16.to_le_bytes()); // Bytes per sample
+    wav.extend_from_slice(&16u16.to_le_bytes()); // Bits per sample
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+
+    for sample in samples {
+        let value = (sample.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16;
+        wav.extend_from_slice(&value.to_le_bytes());
+    }
+
+    wav
+}
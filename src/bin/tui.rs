@@ -0,0 +1,329 @@
+//! Terminal frontend, for when there's no GPU or only an SSH session. Draws the framebuffer with
+//! half-block characters, two pixels per cell, next to the registers and disassembly.
+
+use std::io::{self, Stdout, Write};
+use std::time::{Duration, Instant, SystemTime};
+use std::{env, fs, panic, process};
+
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::{cursor, execute, queue, style, terminal};
+use wasm_chip8::cpu::{Cpu, CpuError};
+use wasm_chip8::disassembler::{generate_disassembly, highlight};
+use wasm_chip8::roms;
+use wasm_chip8::scheduler::{Scheduler, TIMER_HZ};
+
+// Most terminals only report key presses and repeats, so a key counts as held for this long after
+// it was last reported. Long enough to bridge the delay before key repeat kicks in.
+const KEY_HOLD: Duration = Duration::from_millis(250);
+
+// Same layout as the graphical frontend, CHIP-8 key 0 is X and so on
+const KEYS: [char; 16] = [
+    'x', '1', '2', '3', 'q', 'w', 'e', 'a', 's', 'd', 'z', 'c', '4', 'r', 'f', 'v',
+];
+
+const USAGE: &str = "Usage: chip8-tui [--ips N] [ROM]\n\
+    ROM is the name of a built-in ROM or a path to a file, TETRIS by default.";
+
+/// Tracks which CHIP-8 keys are held, from release events if the terminal sends them and by
+/// timing out presses otherwise.
+struct Keypad {
+    // When each key was last reported pressed, cleared on release
+    pressed: [Option<Instant>; 16],
+    releases: bool,
+}
+
+impl Keypad {
+    fn press(&mut self, key: usize, now: Instant) {
+        self.pressed[key] = Some(now);
+    }
+
+    fn release(&mut self, key: usize) {
+        self.pressed[key] = None;
+    }
+
+    fn update(&self, cpu: &mut Cpu, now: Instant) {
+        for (key, pressed) in self.pressed.iter().enumerate() {
+            let held = pressed.is_some_and(|at| self.releases || now < at + KEY_HOLD);
+            cpu.set_key(key, held);
+        }
+    }
+}
+
+fn chip8_key(code: KeyCode) -> Option<usize> {
+    match code {
+        KeyCode::Char(c) => {
+            let c = c.to_ascii_lowercase();
+            // QWERTZ keyboards have Y where Z is
+            let c = if c == 'y' { 'z' } else { c };
+            KEYS.iter().position(|key| *key == c)
+        }
+        _ => None,
+    }
+}
+
+struct Options {
+    name: String,
+    rom: Vec<u8>,
+    ips: Option<u32>,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut rom = "TETRIS".to_string();
+    let mut ips = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--ips" => {
+                let value = args.next().ok_or("--ips needs a value")?;
+                ips = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("Invalid instructions per second: {}", value))?,
+                );
+            }
+            "-h" | "--help" => return Err(String::new()),
+            _ => rom = arg,
+        }
+    }
+
+    let builtin = rom.to_uppercase();
+    if roms::ROMS.contains(&builtin.as_str()) {
+        return Ok(Options {
+            rom: roms::get_bytes(&builtin),
+            name: builtin,
+            ips,
+        });
+    }
+    let bytes = fs::read(&rom).map_err(|err| format!("Can't read {}: {}", rom, err))?;
+    Ok(Options {
+        name: rom,
+        rom: bytes,
+        ips,
+    })
+}
+
+fn main() {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(err) => {
+            if !err.is_empty() {
+                eprintln!("{}", err);
+            }
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    let mut cpu = Cpu::new();
+    let seed = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |since| since.as_nanos() as u64);
+    cpu.seed_rng(seed);
+    if let Err(err) = cpu.init_mem(&options.rom) {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+    let ips = options
+        .ips
+        .unwrap_or_else(|| roms::default_ips(&options.name));
+    let mut scheduler = Scheduler::new(ips);
+
+    let mut stdout = io::stdout();
+    let releases = enter_terminal(&mut stdout).expect("Failed to set up the terminal");
+    // Leave the terminal usable even if the emulator panics
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let _ = leave_terminal(&mut io::stdout(), releases);
+        default_hook(info);
+    }));
+
+    let result = run(
+        &mut stdout,
+        &mut cpu,
+        &mut scheduler,
+        &options.name,
+        releases,
+    );
+    let _ = leave_terminal(&mut stdout, releases);
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
+
+/// Switches to the alternate screen in raw mode. Returns whether the terminal reports key releases.
+fn enter_terminal(stdout: &mut Stdout) -> io::Result<bool> {
+    terminal::enable_raw_mode()?;
+    execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)?;
+    let releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+    if releases {
+        execute!(
+            stdout,
+            PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+        )?;
+    }
+    Ok(releases)
+}
+
+fn leave_terminal(stdout: &mut Stdout, releases: bool) -> io::Result<()> {
+    if releases {
+        execute!(stdout, PopKeyboardEnhancementFlags)?;
+    }
+    execute!(stdout, cursor::Show, terminal::LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()
+}
+
+fn run(
+    stdout: &mut Stdout,
+    cpu: &mut Cpu,
+    scheduler: &mut Scheduler,
+    name: &str,
+    releases: bool,
+) -> io::Result<()> {
+    let frame = Duration::from_secs(1) / TIMER_HZ;
+    let mut keypad = Keypad {
+        pressed: [None; 16],
+        releases,
+    };
+    let mut paused = false;
+    let mut error: Option<CpuError> = None;
+    let mut last = Instant::now();
+
+    execute!(stdout, terminal::Clear(terminal::ClearType::All))?;
+    loop {
+        let start = Instant::now();
+        let mut step = None;
+
+        while event::poll(Duration::ZERO)? {
+            let key = match event::read()? {
+                Event::Key(key) => key,
+                Event::Resize(..) => {
+                    execute!(stdout, terminal::Clear(terminal::ClearType::All))?;
+                    continue;
+                }
+                _ => continue,
+            };
+            let KeyEvent {
+                code,
+                modifiers,
+                kind,
+                ..
+            } = key;
+
+            if code == KeyCode::Char('c') && modifiers.contains(KeyModifiers::CONTROL) {
+                return Ok(());
+            }
+            if let Some(chip8) = chip8_key(code) {
+                match kind {
+                    KeyEventKind::Release => keypad.release(chip8),
+                    _ => keypad.press(chip8, start),
+                }
+                continue;
+            }
+            if kind == KeyEventKind::Release {
+                continue;
+            }
+            match code {
+                KeyCode::Esc => return Ok(()),
+                KeyCode::Char('p') if error.is_none() => paused = !paused,
+                KeyCode::Char('n') if paused => step = Some(Step::Frame),
+                KeyCode::Char('i') if paused => step = Some(Step::Instruction),
+                _ => {}
+            }
+        }
+
+        keypad.update(cpu, start);
+        let dt = start.duration_since(last).as_secs_f64();
+        last = start;
+        let result = match step {
+            _ if error.is_some() => Ok(()),
+            Some(Step::Frame) => scheduler.tick(cpu),
+            Some(Step::Instruction) => cpu.step(),
+            None if paused => Ok(()),
+            None => scheduler.update(cpu, dt, 1.0).map(|_| ()),
+        };
+        if let Err(err) = result {
+            error = Some(err);
+            paused = true;
+        }
+
+        draw(stdout, cpu, name, paused, &error)?;
+
+        if let Some(remaining) = frame.checked_sub(start.elapsed()) {
+            std::thread::sleep(remaining);
+        }
+    }
+}
+
+enum Step {
+    Frame,
+    Instruction,
+}
+
+fn draw(
+    stdout: &mut Stdout,
+    cpu: &mut Cpu,
+    name: &str,
+    paused: bool,
+    error: &Option<CpuError>,
+) -> io::Result<()> {
+    let mut left = vec![format!("┌{}┐", "─".repeat(64))];
+    let fb = cpu.get_framebuffer();
+    for y in (0..32).step_by(2) {
+        let row: String = (0..64)
+            .map(|x| match (fb[y * 64 + x], fb[(y + 1) * 64 + x]) {
+                (true, true) => '█',
+                (true, false) => '▀',
+                (false, true) => '▄',
+                (false, false) => ' ',
+            })
+            .collect();
+        left.push(format!("│{}│", row));
+    }
+    left.push(format!("└{}┘", "─".repeat(64)));
+    let status = match error {
+        Some(err) => format!("Halted: {}", err),
+        None if paused => "Paused".to_string(),
+        None => name.to_string(),
+    };
+    left.push(format!(" {}", status));
+    left.push(" P pause  N next frame  I next instruction  Esc quit".to_string());
+
+    let mut right = Vec::new();
+    for (row, regs) in cpu.regs.chunks(4).enumerate() {
+        let regs: Vec<String> = regs
+            .iter()
+            .enumerate()
+            .map(|(idx, reg)| format!("V{:X} {:02X}", row * 4 + idx, reg))
+            .collect();
+        right.push(regs.join("  "));
+    }
+    right.push(format!(
+        "PC {:03X}  I {:03X}  SP {:X}",
+        cpu.pc,
+        cpu.reg_i,
+        cpu.stack.len()
+    ));
+    right.push(format!(
+        "DT {:02X}   ST {:02X}",
+        cpu.reg_delay, cpu.reg_sound
+    ));
+    right.push(String::new());
+    let disassembly = generate_disassembly(cpu, cpu.pc.saturating_sub(4)..cpu.pc + 24);
+    right.extend(highlight(&disassembly, 2).lines().map(str::to_string));
+
+    for row in 0..left.len().max(right.len()) {
+        let left = left.get(row).map_or("", String::as_str);
+        let right = right.get(row).map_or("", String::as_str);
+        queue!(
+            stdout,
+            cursor::MoveTo(0, row as u16),
+            style::Print(format!("{:<68}{}", left, right)),
+            terminal::Clear(terminal::ClearType::UntilNewLine)
+        )?;
+    }
+    stdout.flush()
+}
@@ -0,0 +1,25 @@
+precision mediump float;
+varying vec4 color;
+varying vec2 uv;
+
+uniform sampler2D Texture;
+uniform vec2 SourceSize;
+uniform float BloomRadius;
+uniform float BloomStrength;
+
+// Adds a blurred copy of the image on top, so lit pixels glow into their surroundings
+void main() {
+    vec3 res = texture2D(Texture, uv).rgb;
+
+    vec2 spread = BloomRadius / SourceSize;
+    vec3 glow = vec3(0.0);
+    for (int x = -2; x <= 2; x++) {
+        for (int y = -2; y <= 2; y++) {
+            float weight = 1.0 - length(vec2(x, y)) / 3.0;
+            glow += texture2D(Texture, uv + vec2(x, y) * spread * 0.5).rgb * weight;
+        }
+    }
+    glow /= 9.0;
+
+    gl_FragColor = vec4((res + glow * BloomStrength) * color.rgb, 1.0);
+}
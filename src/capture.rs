@@ -0,0 +1,133 @@
+use crate::display::Palette;
+use macroquad::prelude::*;
+use std::borrow::Cow;
+
+/// Encodes `img` as an RGBA PNG.
+pub fn encode_png(img: &Image) -> Vec<u8> {
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, u32::from(img.width), u32::from(img.height));
+    encoder.set_color(png::ColorType::RGBA);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    // Writing into a Vec can't fail
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(&img.bytes).unwrap();
+    drop(writer);
+
+    png
+}
+
+/// Scales `img` up by an integer `factor` without any smoothing.
+pub fn scale_image(img: &Image, factor: u16) -> Image {
+    let (width, height) = (img.width * factor, img.height * factor);
+    let mut scaled = Image::gen_image_color(width, height, BLACK);
+    for y in 0..u32::from(height) {
+        for x in 0..u32::from(width) {
+            let color = img.get_pixel(x / u32::from(factor), y / u32::from(factor));
+            scaled.set_pixel(x, y, color);
+        }
+    }
+    scaled
+}
+
+/// Turns an image read back from the GPU, which starts at the bottom row, the right way up.
+pub fn flip_vertical(img: &mut Image) {
+    let row = img.width as usize * 4;
+    let (mut top, mut bottom) = (0, img.height as usize);
+    while top + 1 < bottom {
+        bottom -= 1;
+        let (upper, lower) = img.bytes.split_at_mut(bottom * row);
+        upper[top * row..(top + 1) * row].swap_with_slice(&mut lower[..row]);
+        top += 1;
+    }
+}
+
+const MIN_DELAY: u32 = 2;
+
+/// When an emulated frame starts, in the 1/100s units of GIF delays. Rounding the frame
+/// boundaries rather than each frame's length keeps the total in sync with 60Hz.
+fn centiseconds(frame: u32) -> u32 {
+    (frame * 100 + 30) / 60
+}
+
+/// Records emulated frames into an animated GIF, indexed with the first two palette colours.
+/// Identical frames in a row are merged into one, which keeps static screens small.
+pub struct GifRecorder {
+    encoder: gif::Encoder<Vec<u8>>,
+    scale: u16,
+    pending: Option<Vec<u8>>,
+    // Emulated frames recorded so far, and the one the pending image was first shown at
+    frames: u32,
+    pending_since: u32,
+}
+
+impl GifRecorder {
+    pub fn new(palette: &Palette, scale: u16) -> Self {
+        let mut colors = Vec::new();
+        for color in palette.colors[..2].iter() {
+            let [r, g, b, _]: [u8; 4] = (*color).into();
+            colors.extend_from_slice(&[r, g, b]);
+        }
+
+        // Writing into a Vec can't fail
+        let mut encoder = gif::Encoder::new(Vec::new(), 64 * scale, 32 * scale, &colors).unwrap();
+        encoder.set_repeat(gif::Repeat::Infinite).unwrap();
+
+        Self {
+            encoder,
+            scale,
+            pending: None,
+            frames: 0,
+            pending_since: 0,
+        }
+    }
+
+    /// Adds the framebuffer at the end of an emulated frame.
+    pub fn push(&mut self, fb: &[bool; 32 * 64]) {
+        let scale = usize::from(self.scale);
+        let mut pixels = Vec::with_capacity(fb.len() * scale * scale);
+        for row in fb.chunks(64) {
+            for _ in 0..scale {
+                for lit in row {
+                    pixels.extend(std::iter::repeat_n(*lit as u8, scale));
+                }
+            }
+        }
+
+        if self.pending.as_ref() != Some(&pixels) {
+            // Browsers slow down frames shorter than 2/100s, so a frame that would be
+            // shown for less than that gets replaced by the next one instead
+            if centiseconds(self.frames) - centiseconds(self.pending_since) >= MIN_DELAY {
+                self.flush();
+                self.pending_since = self.frames;
+            }
+            self.pending = Some(pixels);
+        }
+        self.frames += 1;
+    }
+
+    /// Number of emulated frames recorded.
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        self.flush();
+        self.encoder.into_inner().unwrap()
+    }
+
+    fn flush(&mut self) {
+        if let Some(pixels) = self.pending.take() {
+            let delay = centiseconds(self.frames) - centiseconds(self.pending_since);
+
+            let frame = gif::Frame {
+                width: 64 * self.scale,
+                height: 32 * self.scale,
+                delay: delay as u16,
+                buffer: Cow::Owned(pixels),
+                ..Default::default()
+            };
+            self.encoder.write_frame(&frame).unwrap();
+        }
+    }
+}
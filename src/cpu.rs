@@ -1,4 +1,10 @@
-use macroquad::rand::gen_range;
+use std::fmt;
+
+pub const STACK_SIZE: usize = 16;
+
+/// Machine cycles (8 clocks of the 1.76MHz CDP1802) the COSMAC VIP runs per 60Hz frame,
+/// minus what the display DMA and interrupt routine take for themselves.
+pub const VIP_CYCLES_PER_FRAME: u32 = 3668 - 1024 - 70;
 
 pub struct Cpu {
     pub mem: [u8; 0x1000],
@@ -13,10 +19,34 @@ pub struct Cpu {
     pub framebuffer: [bool; 32 * 64],
 
     pub keymap: [bool; 0x10],
-    block_release: bool,
+    key_wait: Option<KeyWait>,
 
     pub st_compat: bool,
     pub sh_compat: bool,
+    pub vip_timing: bool,
+    pub display_wait: bool,
+    vblank: bool,
+
+    /// XO-CHIP 1-bit audio pattern, played instead of the beep once loaded with F002
+    pub audio_pattern: Option<[u8; 16]>,
+    /// XO-CHIP playback rate of the audio pattern, 64 is 4000 bits per second
+    pub pitch: u8,
+    waiting_vblank: bool,
+
+    /// VIP machine cycles consumed so far, only counted with `vip_timing`
+    pub cycles: u64,
+    // State of the CXKK random number generator, see `seed_rng`
+    rng: u64,
+}
+
+/// How far FX0A has got waiting for a key.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum KeyWait {
+    /// Waiting for a key to be pressed. Keys in `held` were already down when the wait started and
+    /// only count once they've been released and pressed again.
+    Press { x: usize, held: u16 },
+    /// `key` was pressed and stored in VX, waiting for it to be released
+    Release { x: usize, key: u8 },
 }
 
 pub enum ArgType {
@@ -25,6 +55,56 @@ pub enum ArgType {
     Xyn,
 }
 
+#[derive(Debug, PartialEq)]
+pub enum CpuError {
+    RomTooLarge(usize),
+    PcOutOfBounds(usize),
+    MemOutOfBounds(usize),
+    StackOverflow,
+    StackUnderflow,
+    UnknownInstruction(u16),
+}
+
+impl fmt::Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CpuError::RomTooLarge(len) => write!(f, "ROM is too large: {} bytes", len),
+            CpuError::PcOutOfBounds(pc) => write!(f, "PC out of bounds: 0x{:X}", pc),
+            CpuError::MemOutOfBounds(addr) => {
+                write!(f, "Memory access out of bounds: 0x{:X}", addr)
+            }
+            CpuError::StackOverflow => write!(f, "Stack overflow"),
+            CpuError::StackUnderflow => write!(f, "Return with empty stack"),
+            CpuError::UnknownInstruction(instr) => {
+                write!(f, "Unimplemented instruction: {:04X}", instr)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CpuError {}
+
+/// Approximate cost of an instruction in the original VIP interpreter, including fetch and decode.
+fn vip_cycles(nibbles: [u8; 4]) -> u32 {
+    match nibbles {
+        [0x0, 0x0, 0xE, 0x0] => 24,
+        [0x0, 0x0, 0xE, 0xE] | [0x1, ..] | [0x2, ..] | [0xB, ..] => 23,
+        [0x3, ..] | [0x4, ..] | [0xA, ..] => 12,
+        [0x5, ..] | [0x9, ..] | [0xE, ..] => 16,
+        [0x6, ..] => 6,
+        [0x7, ..] => 10,
+        [0x8, ..] => 44,
+        [0xC, ..] => 36,
+        // Sprites are shifted into place byte by byte, which makes DXYN by far the slowest
+        [0xD, _, _, n] => 150 + 100 * u32::from(n),
+        [0xF, _, 0x1, 0xE] => 19,
+        [0xF, _, 0x2, 0x9] => 20,
+        [0xF, _, 0x3, 0x3] => 204,
+        [0xF, x, 0x5, 0x5] | [0xF, x, 0x6, 0x5] => 14 + 14 * (u32::from(x) + 1),
+        _ => 10,
+    }
+}
+
 enum PcMode {
     Step,
     Skip,
@@ -39,6 +119,12 @@ const DIGITS: [u8; 0x50] = [
     0xF0, 0xE0, 0x90, 0x90, 0x90, 0xE0, 0xF0, 0x80, 0xF0, 0x80, 0xF0, 0xF0, 0x80, 0xF0, 0x80, 0x80,
 ];
 
+impl Default for Cpu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Cpu {
     pub fn new() -> Self {
         Self {
@@ -53,14 +139,28 @@ impl Cpu {
             framebuffer: [false; 64 * 32],
 
             keymap: [false; 0x10],
-            block_release: false,
+            key_wait: None,
 
             st_compat: false,
             sh_compat: false,
+            vip_timing: false,
+            display_wait: false,
+            vblank: false,
+
+            audio_pattern: None,
+            pitch: 64,
+            waiting_vblank: false,
+
+            cycles: 0,
+            rng: 0,
         }
     }
 
-    pub fn init_mem(&mut self, rom: &[u8]) {
+    pub fn init_mem(&mut self, rom: &[u8]) -> Result<(), CpuError> {
+        if rom.len() > self.mem.len() - 0x200 {
+            return Err(CpuError::RomTooLarge(rom.len()));
+        }
+
         for (idx, byte) in DIGITS.iter().enumerate() {
             self.mem[idx] = *byte;
         }
@@ -68,6 +168,8 @@ impl Cpu {
         for (idx, byte) in rom.iter().enumerate() {
             self.mem[0x200 + idx] = *byte;
         }
+
+        Ok(())
     }
 
     pub fn get_framebuffer(&self) -> &[bool; 32 * 64] {
@@ -79,19 +181,75 @@ impl Cpu {
         self.reg_sound = self.reg_sound.saturating_sub(1);
     }
 
+    /// Signals a frame boundary, which DXYN waits for when `display_wait` is set.
+    pub fn vblank(&mut self) {
+        self.vblank = true;
+    }
+
+    pub fn waiting_for_vblank(&self) -> bool {
+        self.waiting_vblank
+    }
+
+    /// Set while FX0A is blocking the CPU.
+    pub fn key_wait(&self) -> Option<KeyWait> {
+        self.key_wait
+    }
+
+    /// Seeds the generator behind CXKK. The same seed and input give the same random numbers.
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = seed;
+    }
+
+    /// Hash of the whole machine state, to check two runs stayed in sync.
+    pub fn checksum(&self) -> u32 {
+        // FNV-1a
+        let mut hash: u32 = 0x811C_9DC5;
+        let mut feed = |bytes: &[u8]| {
+            for byte in bytes {
+                hash = (hash ^ u32::from(*byte)).wrapping_mul(0x0100_0193);
+            }
+        };
+        feed(&self.mem);
+        for addr in self.stack.iter() {
+            feed(&(*addr as u16).to_le_bytes());
+        }
+        feed(&self.regs);
+        feed(&(self.reg_i as u16).to_le_bytes());
+        feed(&[self.reg_delay, self.reg_sound]);
+        feed(&(self.pc as u16).to_le_bytes());
+        for pixels in self.framebuffer.chunks(8) {
+            feed(&[pixels.iter().fold(0, |byte, lit| byte << 1 | *lit as u8)]);
+        }
+        let key_wait = match self.key_wait {
+            None => [0, 0, 0, 0],
+            Some(KeyWait::Press { x, held }) => [1, x as u8, held as u8, (held >> 8) as u8],
+            Some(KeyWait::Release { x, key }) => [2, x as u8, key, 0],
+        };
+        feed(&key_wait);
+        feed(&[self.vblank as u8, self.waiting_vblank as u8, self.pitch]);
+        feed(&self.audio_pattern.unwrap_or([0; 16]));
+        feed(&self.cycles.to_le_bytes());
+        feed(&self.rng.to_le_bytes());
+        hash
+    }
+
     pub fn set_key(&mut self, key: usize, value: bool) {
         self.keymap[key] = value;
     }
 
-    pub fn step(&mut self) {
+    pub fn step(&mut self) -> Result<(), CpuError> {
+        if self.pc + 1 >= self.mem.len() {
+            return Err(CpuError::PcOutOfBounds(self.pc));
+        }
+
         let nibbles = self.get_instr_addr(self.pc);
 
         match nibbles {
             [0x0, 0x0, 0xE, 0x0] => self.clear(),
-            [0x0, 0x0, 0xE, 0xE] => self.ret(),
+            [0x0, 0x0, 0xE, 0xE] => self.ret()?,
             [0x0, 0x0, 0xF, 0xA] => self.set_st_compat(),
             [0x1, _, _, _] => self.jump_nnn(),
-            [0x2, _, _, _] => self.call_nnn(),
+            [0x2, _, _, _] => self.call_nnn()?,
             [0x3, _, _, _] => self.skip_eq_xkk(),
             [0x4, _, _, _] => self.skip_neq_xkk(),
             [0x5, _, _, 0x0] => self.skip_eq_xy(),
@@ -110,24 +268,34 @@ impl Cpu {
             [0xA, _, _, _] => self.load_i_nnn(),
             [0xB, _, _, _] => self.jump_nnn_offset(),
             [0xC, _, _, _] => self.rand_x_kk(),
-            [0xD, _, _, _] => self.draw_xyn(),
+            [0xD, _, _, _] => self.draw_xyn()?,
             [0xE, _, 0x9, 0xE] => self.skip_key_x(),
             [0xE, _, 0xA, 0x1] => self.skip_nkey_x(),
+            [0xF, 0x0, 0x0, 0x2] => self.load_audio()?,
             [0xF, _, 0x0, 0x7] => self.load_x_dt(),
             [0xF, _, 0x0, 0xA] => self.block_key_x(),
             [0xF, _, 0x1, 0x5] => self.load_dt_x(),
             [0xF, _, 0x1, 0x8] => self.load_st_x(),
             [0xF, _, 0x1, 0xE] => self.add_i_x(),
             [0xF, _, 0x2, 0x9] => self.load_i_digit_x(),
-            [0xF, _, 0x3, 0x3] => self.store_bcd_x(),
-            [0xF, _, 0x5, 0x5] => self.store_vx(),
-            [0xF, _, 0x6, 0x5] => self.restore_vx(),
+            [0xF, _, 0x3, 0x3] => self.store_bcd_x()?,
+            [0xF, _, 0x3, 0xA] => self.load_pitch_x(),
+            [0xF, _, 0x5, 0x5] => self.store_vx()?,
+            [0xF, _, 0x6, 0x5] => self.restore_vx()?,
+            // Machine code routines (0NNN) are not supported and leave the CPU stuck in place
+            [0x0, _, _, _] => {}
             _ => {
-                if nibbles[0] != 0x0 {
-                    self.unimpl_panic();
-                }
+                let instr = u16::from_be_bytes([self.mem[self.pc], self.mem[self.pc + 1]]);
+                return Err(CpuError::UnknownInstruction(instr));
             }
         }
+
+        // A draw stalled by `display_wait` runs again next frame, and is charged then
+        if self.vip_timing && !self.waiting_vblank {
+            self.cycles += u64::from(vip_cycles(nibbles));
+        }
+
+        Ok(())
     }
 
     fn clear(&mut self) {
@@ -135,10 +303,11 @@ impl Cpu {
         self.set_pc(PcMode::Step);
     }
 
-    fn ret(&mut self) {
-        let addr = self.stack.pop().unwrap();
+    fn ret(&mut self) -> Result<(), CpuError> {
+        let addr = self.stack.pop().ok_or(CpuError::StackUnderflow)?;
         self.set_pc(PcMode::Jump(addr));
         self.set_pc(PcMode::Step);
+        Ok(())
     }
 
     fn set_st_compat(&mut self) {
@@ -151,10 +320,15 @@ impl Cpu {
         self.set_pc(PcMode::Jump(args[0]));
     }
 
-    fn call_nnn(&mut self) {
+    fn call_nnn(&mut self) -> Result<(), CpuError> {
+        if self.stack.len() == STACK_SIZE {
+            return Err(CpuError::StackOverflow);
+        }
+
         let args = self.get_args(ArgType::Nnn);
         self.stack.push(self.pc);
         self.set_pc(PcMode::Jump(args[0]));
+        Ok(())
     }
 
     fn skip_eq_xkk(&mut self) {
@@ -248,17 +422,14 @@ impl Cpu {
     fn shr_xy(&mut self) {
         let args = self.get_args(ArgType::Xyn);
 
-        let (res, wrap) = if self.sh_compat {
+        let value = if self.sh_compat {
             self.regs[args[0]]
         } else {
             self.regs[args[1]]
-        }
-        .overflowing_shr(1);
+        };
 
-        self.regs[args[0]] = res & !0x80;
-        if wrap {
-            self.regs[0xF] = 1;
-        }
+        self.regs[args[0]] = value >> 1;
+        self.regs[0xF] = value & 0x1;
         self.set_pc(PcMode::Step);
     }
 
@@ -267,6 +438,8 @@ impl Cpu {
         let (res, wrap) = self.regs[args[1]].overflowing_sub(self.regs[args[0]]);
         self.regs[args[0]] = res;
         if wrap {
+            self.regs[0xF] = 0;
+        } else {
             self.regs[0xF] = 1;
         }
         self.set_pc(PcMode::Step);
@@ -275,17 +448,14 @@ impl Cpu {
     fn shl_xy(&mut self) {
         let args = self.get_args(ArgType::Xyn);
 
-        let (res, wrap) = if self.sh_compat {
+        let value = if self.sh_compat {
             self.regs[args[0]]
         } else {
             self.regs[args[1]]
-        }
-        .overflowing_shl(1);
+        };
 
-        self.regs[args[0]] = res & !0x1;
-        if wrap {
-            self.regs[0xF] = 1;
-        }
+        self.regs[args[0]] = value << 1;
+        self.regs[0xF] = value >> 7;
         self.set_pc(PcMode::Step);
     }
 
@@ -311,20 +481,43 @@ impl Cpu {
 
     fn rand_x_kk(&mut self) {
         let args = self.get_args(ArgType::Xkk);
-        self.regs[args[0]] = gen_range(0, 255) & args[1] as u8;
+        self.regs[args[0]] = self.next_random() & args[1] as u8;
         self.set_pc(PcMode::Step);
     }
 
-    fn draw_xyn(&mut self) {
+    fn next_random(&mut self) -> u8 {
+        // SplitMix64
+        self.rng = self.rng.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        ((z ^ (z >> 31)) >> 56) as u8
+    }
+
+    fn draw_xyn(&mut self) -> Result<(), CpuError> {
+        // Like the original interpreter, stall until the next frame before drawing
+        if self.display_wait {
+            if !self.waiting_vblank {
+                self.waiting_vblank = true;
+                self.vblank = false;
+            }
+            if !self.vblank {
+                return Ok(());
+            }
+            self.waiting_vblank = false;
+        }
+
         let args = self.get_args(ArgType::Xyn);
+        let (vx, vy) = (self.regs[args[0]] as usize, self.regs[args[1]] as usize);
+        self.check_mem(self.reg_i, args[2])?;
 
         self.regs[0xF] = 0;
 
         for i in 0..args[2] {
-            let y = (self.regs[args[1]] + i as u8) as usize % 32;
+            let y = (vy + i) % 32;
             for j in 0..8 {
-                let x = (self.regs[args[0]] + j as u8) as usize % 64;
-                let pixel = (self.mem[self.reg_i + i as usize] & (0x80 >> j)) == (0x80 >> j);
+                let x = (vx + j) % 64;
+                let pixel = (self.mem[self.reg_i + i] & (0x80 >> j)) == (0x80 >> j);
 
                 let idx = y * 64 + x;
 
@@ -337,11 +530,12 @@ impl Cpu {
         }
 
         self.set_pc(PcMode::Step);
+        Ok(())
     }
 
     fn skip_key_x(&mut self) {
         let args = self.get_args(ArgType::Xyn);
-        if self.keymap[self.regs[args[0]] as usize] {
+        if self.keymap[self.regs[args[0]] as usize & 0xF] {
             self.set_pc(PcMode::Skip);
         } else {
             self.set_pc(PcMode::Step);
@@ -350,7 +544,7 @@ impl Cpu {
 
     fn skip_nkey_x(&mut self) {
         let args = self.get_args(ArgType::Xyn);
-        if !self.keymap[self.regs[args[0]] as usize] {
+        if !self.keymap[self.regs[args[0]] as usize & 0xF] {
             self.set_pc(PcMode::Skip);
         } else {
             self.set_pc(PcMode::Step);
@@ -365,13 +559,33 @@ impl Cpu {
 
     fn block_key_x(&mut self) {
         let args = self.get_args(ArgType::Xyn);
-        if self.keymap.iter().any(|e| *e) {
-            self.block_release = true;
-            self.regs[args[0]] = self.keymap.iter().position(|e| *e).unwrap() as u8;
-        } else if self.block_release {
-            self.block_release = false;
-            self.set_pc(PcMode::Step);
-        }
+        let x = args[0];
+        let keys = (0..0x10)
+            .filter(|key| self.keymap[*key])
+            .fold(0u16, |keys, key| keys | 1 << key);
+        self.key_wait = match self.key_wait {
+            None => Some(KeyWait::Press { x, held: keys }),
+            Some(KeyWait::Press { held, .. }) => {
+                // Forget keys that were let go, so pressing them again counts
+                let held = held & keys;
+                let pressed = keys & !held;
+                if pressed != 0 {
+                    let key = pressed.trailing_zeros() as u8;
+                    self.regs[x] = key;
+                    Some(KeyWait::Release { x, key })
+                } else {
+                    Some(KeyWait::Press { x, held })
+                }
+            }
+            Some(KeyWait::Release { x, key }) => {
+                if self.keymap[usize::from(key)] {
+                    Some(KeyWait::Release { x, key })
+                } else {
+                    self.set_pc(PcMode::Step);
+                    None
+                }
+            }
+        };
     }
 
     fn load_dt_x(&mut self) {
@@ -398,19 +612,37 @@ impl Cpu {
         self.set_pc(PcMode::Step);
     }
 
-    fn store_bcd_x(&mut self) {
+    fn load_audio(&mut self) -> Result<(), CpuError> {
+        self.check_mem(self.reg_i, 16)?;
+        let mut pattern = [0; 16];
+        pattern.copy_from_slice(&self.mem[self.reg_i..self.reg_i + 16]);
+        self.audio_pattern = Some(pattern);
+        self.set_pc(PcMode::Step);
+        Ok(())
+    }
+
+    fn load_pitch_x(&mut self) {
+        let args = self.get_args(ArgType::Xyn);
+        self.pitch = self.regs[args[0]];
+        self.set_pc(PcMode::Step);
+    }
+
+    fn store_bcd_x(&mut self) -> Result<(), CpuError> {
         let args = self.get_args(ArgType::Xyn);
         let num = self.regs[args[0]];
+        self.check_mem(self.reg_i, 3)?;
 
         self.mem[self.reg_i] = num / 100;
         self.mem[self.reg_i + 1] = (num % 100) / 10;
         self.mem[self.reg_i + 2] = num % 10;
 
         self.set_pc(PcMode::Step);
+        Ok(())
     }
 
-    fn store_vx(&mut self) {
+    fn store_vx(&mut self) -> Result<(), CpuError> {
         let args = self.get_args(ArgType::Xyn);
+        self.check_mem(self.reg_i, args[0] + 1)?;
         for idx in 0..=args[0] {
             self.mem[self.reg_i] = self.regs[idx];
             self.reg_i += 1;
@@ -420,10 +652,12 @@ impl Cpu {
             self.reg_i -= args[0] + 1;
         }
         self.set_pc(PcMode::Step);
+        Ok(())
     }
 
-    fn restore_vx(&mut self) {
+    fn restore_vx(&mut self) -> Result<(), CpuError> {
         let args = self.get_args(ArgType::Xyn);
+        self.check_mem(self.reg_i, args[0] + 1)?;
         for idx in 0..=args[0] {
             self.regs[idx] = self.mem[self.reg_i];
             self.reg_i += 1;
@@ -432,6 +666,15 @@ impl Cpu {
             self.reg_i -= args[0] + 1;
         }
         self.set_pc(PcMode::Step);
+        Ok(())
+    }
+
+    fn check_mem(&self, addr: usize, len: usize) -> Result<(), CpuError> {
+        if addr + len > self.mem.len() {
+            Err(CpuError::MemOutOfBounds(addr + len - 1))
+        } else {
+            Ok(())
+        }
     }
 
     fn set_pc(&mut self, pc_mode: PcMode) {
@@ -481,14 +724,4 @@ impl Cpu {
 
         args
     }
-
-    fn unimpl_panic(&self) {
-        let n = self.get_instr_addr(self.pc);
-        println!(
-            "Unimplemented instruction: {:X}{:X}{:X}{:X}",
-            n[0], n[1], n[2], n[3]
-        ); // clean code :)
-
-        panic!();
-    }
 }
@@ -5,6 +5,9 @@ pub fn generate_disassembly(cpu: &mut Cpu, range: Range<usize>) -> String {
     let mut disassembly = String::new();
     for pc in range.collect::<Vec<usize>>().chunks(2) {
         let pc = pc[0];
+        if pc + 1 >= cpu.mem.len() {
+            break;
+        }
         let nibbles = cpu.get_instr_addr(pc);
         let nnn = cpu.get_args_addr(ArgType::Nnn, pc);
         let xkk = cpu.get_args_addr(ArgType::Xkk, pc);
@@ -37,6 +40,7 @@ pub fn generate_disassembly(cpu: &mut Cpu, range: Range<usize>) -> String {
             [0xD, _, _, _] => format!("DRW V{:X}, V{:X}, 0x{:X}", xyn[0], xyn[1], xyn[2]),
             [0xE, _, 0x9, 0xE] => format!("SKP V{:X}", xyn[0]),
             [0xE, _, 0xA, 0x1] => format!("SKNP V{:X}", xyn[0]),
+            [0xF, 0x0, 0x0, 0x2] => "AUDIO [I]".to_string(),
             [0xF, _, 0x0, 0x7] => format!("LD V{:X}, DT", xyn[0]),
             [0xF, _, 0x0, 0xA] => format!("LD V{:X}, K", xyn[0]),
             [0xF, _, 0x1, 0x5] => format!("LD DT, V{:X}", xyn[0]),
@@ -44,6 +48,7 @@ pub fn generate_disassembly(cpu: &mut Cpu, range: Range<usize>) -> String {
             [0xF, _, 0x1, 0xE] => format!("ADD I, V{:X}", xyn[0]),
             [0xF, _, 0x2, 0x9] => format!("LD F, V{:X}", xyn[0]),
             [0xF, _, 0x3, 0x3] => format!("LD B, V{:X}", xyn[0]),
+            [0xF, _, 0x3, 0xA] => format!("LD PITCH, V{:X}", xyn[0]),
             [0xF, _, 0x5, 0x5] => format!("LD [I], V{:X}", xyn[0]),
             [0xF, _, 0x6, 0x5] => format!("LD V{:X}, [I]", xyn[0]),
             _ => format!(
@@ -0,0 +1,181 @@
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Display colours, indexed by which planes a pixel is lit in: background, first plane, second
+/// plane and both. Plain CHIP-8 only ever uses the first two.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Palette {
+    pub colors: [Color; 4],
+}
+
+impl Palette {
+    /// Parses four `#RRGGBB` colours, the leading `#` being optional.
+    pub fn from_hex(hex: &[&str; 4]) -> Option<Self> {
+        let mut colors = [BLACK; 4];
+        for (color, hex) in colors.iter_mut().zip(hex.iter()) {
+            *color = parse_hex(hex)?;
+        }
+        Some(Self { colors })
+    }
+
+    pub fn to_hex(&self) -> [String; 4] {
+        let mut hex = [String::new(), String::new(), String::new(), String::new()];
+        for (hex, color) in hex.iter_mut().zip(self.colors.iter()) {
+            let [r, g, b, _] = color_to_rgba(*color);
+            *hex = format!("#{:02X}{:02X}{:02X}", r, g, b);
+        }
+        hex
+    }
+}
+
+pub const PALETTES: [(&str, [&str; 4]); 5] = [
+    ("Classic", ["#000000", "#FFFFFF", "#AAAAAA", "#555555"]),
+    (
+        "Green phosphor",
+        ["#0A1A0C", "#33FF66", "#1F9C3E", "#146629"],
+    ),
+    ("Amber", ["#1A0F00", "#FFB000", "#B37B00", "#664600"]),
+    ("LCD", ["#9BBC0F", "#0F380F", "#306230", "#8BAC0F"]),
+    ("Octo", ["#996600", "#FFCC00", "#FF6600", "#662200"]),
+];
+
+/// Looks up one of the built-in `PALETTES` by name.
+pub fn preset(name: &str) -> Option<Palette> {
+    PALETTES
+        .iter()
+        .find(|(preset, _)| *preset == name)
+        .and_then(|(_, hex)| Palette::from_hex(hex))
+}
+
+fn parse_hex(hex: &str) -> Option<Color> {
+    let hex = hex.trim().trim_start_matches('#');
+    if hex.len() != 6 {
+        return None;
+    }
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    Some(Color::from_rgba(
+        (value >> 16) as u8,
+        (value >> 8) as u8,
+        value as u8,
+        255,
+    ))
+}
+
+fn color_to_rgba(color: Color) -> [u8; 4] {
+    [
+        (color.r * 255.0).round() as u8,
+        (color.g * 255.0).round() as u8,
+        (color.b * 255.0).round() as u8,
+        (color.a * 255.0).round() as u8,
+    ]
+}
+
+/// How pixels that were just switched off fade out, to hide the flicker of XOR drawing.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Persistence {
+    Off,
+    /// Fade out by a fixed factor every frame
+    Decay,
+    /// Show pixels lit in either of the last two frames
+    OrLastTwo,
+}
+
+impl Persistence {
+    pub const ALL: [Persistence; 3] =
+        [Persistence::Off, Persistence::Decay, Persistence::OrLastTwo];
+}
+
+/// Per-pixel brightness carried over between frames.
+pub struct Phosphor {
+    pub mode: Persistence,
+    /// Fraction of its brightness an unlit pixel keeps each frame in `Persistence::Decay`
+    pub decay: f32,
+    intensity: [f32; 32 * 64],
+}
+
+impl Phosphor {
+    pub fn new(mode: Persistence, decay: f32) -> Self {
+        Self {
+            mode,
+            decay,
+            intensity: [0.0; 32 * 64],
+        }
+    }
+
+    /// Brings the intensities up to date with `fb` after `frames` emulated frames, `previous`
+    /// being the frame before it.
+    fn update(&mut self, fb: &[bool; 32 * 64], previous: &[bool; 32 * 64], frames: u32) {
+        let fade = match self.mode {
+            Persistence::Decay => self.decay.powi(frames as i32),
+            Persistence::Off | Persistence::OrLastTwo => 0.0,
+        };
+
+        for (idx, intensity) in self.intensity.iter_mut().enumerate() {
+            let lit = fb[idx] || (self.mode == Persistence::OrLastTwo && previous[idx]);
+            *intensity = if lit { 1.0 } else { *intensity * fade };
+        }
+    }
+
+    pub fn intensity(&self, x: usize, y: usize) -> f32 {
+        self.intensity[y * 64 + x]
+    }
+}
+
+/// Draws `fb` into `img`, `frames` being the number of emulated frames since the last call.
+/// `previous` is the framebuffer at the frame boundary before `fb`'s, see
+/// `Scheduler::previous_frame`.
+pub fn fb_to_img(
+    img: &mut Image,
+    fb: &[bool; 32 * 64],
+    previous: &[bool; 32 * 64],
+    phosphor: &mut Phosphor,
+    palette: &Palette,
+    frames: u32,
+) {
+    phosphor.update(fb, previous, frames);
+
+    let [background, foreground] = [palette.colors[0], palette.colors[1]];
+    for y in 0..32 {
+        for x in 0..64 {
+            let t = phosphor.intensity(x, y);
+            img.set_pixel(
+                x as u32,
+                y as u32,
+                Color::new(
+                    background.r + (foreground.r - background.r) * t,
+                    background.g + (foreground.g - background.g) * t,
+                    background.b + (foreground.b - background.b) * t,
+                    1.0,
+                ),
+            )
+        }
+    }
+}
+
+/// How the emulator's image is fitted into the window.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ScaleMode {
+    /// The largest whole multiple of the image size that fits, so every pixel is the same size
+    Integer,
+    /// As large as fits while keeping the aspect ratio, with black bars around it
+    Fit,
+    /// Fills the whole window
+    Stretch,
+}
+
+impl ScaleMode {
+    pub const ALL: [ScaleMode; 3] = [ScaleMode::Integer, ScaleMode::Fit, ScaleMode::Stretch];
+}
+
+/// Where to draw an image of `source` size in a `screen` sized window, centered.
+pub fn viewport(mode: ScaleMode, source: Vec2, screen: Vec2) -> Rect {
+    let fit = (screen.x / source.x).min(screen.y / source.y);
+    let size = match mode {
+        // A window smaller than the image still shows it at 1x, cropped
+        ScaleMode::Integer => source * fit.floor().max(1.0),
+        ScaleMode::Fit => source * fit,
+        ScaleMode::Stretch => screen,
+    };
+    let pos = ((screen - size) / 2.0).floor();
+    Rect::new(pos.x, pos.y, size.x, size.y)
+}
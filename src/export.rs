@@ -0,0 +1,40 @@
+//! Hands files created in the emulator to the user: written relative to the working directory
+//! natively, offered as a download in the browser through `js/chip8-download.js`.
+
+use macroquad::miniquad::date;
+
+/// A file name that won't clash with earlier exports, e.g. `TETRIS-1634567890.wav`.
+pub fn file_name(rom: &str, extension: &str) -> String {
+    format!("{}-{}.{}", rom, date::now() as u64, extension)
+}
+
+/// Saves `bytes` to `path`, creating missing directories, and returns where it ended up.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_file(path: &str, bytes: &[u8]) -> Result<String, String> {
+    let error = |err| format!("Couldn't save {}: {}", path, err);
+    if let Some(dir) = std::path::Path::new(path).parent() {
+        std::fs::create_dir_all(dir).map_err(error)?;
+    }
+    std::fs::write(path, bytes).map_err(error)?;
+    Ok(path.to_string())
+}
+
+#[cfg(target_arch = "wasm32")]
+extern "C" {
+    fn chip8_download(name: *const u8, name_len: u32, data: *const u8, data_len: u32);
+}
+
+/// Offers `bytes` as a download named after the last component of `path`.
+#[cfg(target_arch = "wasm32")]
+pub fn save_file(path: &str, bytes: &[u8]) -> Result<String, String> {
+    let name = path.rsplit('/').next().unwrap();
+    unsafe {
+        chip8_download(
+            name.as_ptr(),
+            name.len() as u32,
+            bytes.as_ptr(),
+            bytes.len() as u32,
+        )
+    };
+    Ok(format!("{} (download)", name))
+}
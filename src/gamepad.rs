@@ -0,0 +1,191 @@
+//! Reads gamepads, natively through gilrs and in the browser through the Gamepad API via the small
+//! plugin in `js/chip8-gamepad.js`. All connected gamepads act as one.
+
+use wasm_chip8::keymap::Button;
+
+#[cfg(not(target_arch = "wasm32"))]
+use native::Backend;
+#[cfg(target_arch = "wasm32")]
+use web::Backend;
+
+// How far the stick has to be pushed to count as a direction
+const STICK_THRESHOLD: f32 = 0.5;
+
+pub struct Gamepads {
+    backend: Option<Backend>,
+    held: Vec<Button>,
+    pressed: Vec<Button>,
+}
+
+impl Gamepads {
+    pub fn new() -> Self {
+        Self {
+            backend: Backend::new(),
+            held: Vec::new(),
+            pressed: Vec::new(),
+        }
+    }
+
+    /// Reads the current state, once per frame. Returns a message when a gamepad was connected or
+    /// disconnected.
+    pub fn update(&mut self) -> Option<String> {
+        let backend = self.backend.as_mut()?;
+        let message = backend.hot_plug();
+        let held = backend.held();
+        self.pressed = held
+            .iter()
+            .filter(|button| !self.held.contains(button))
+            .copied()
+            .collect();
+        self.held = held;
+        message
+    }
+
+    pub fn is_down(&self, button: Button) -> bool {
+        self.held.contains(&button)
+    }
+
+    /// A button that went down this frame.
+    pub fn last_pressed(&self) -> Option<Button> {
+        self.pressed.first().copied()
+    }
+}
+
+/// The stick directions for a stick position, with y pointing up.
+fn stick_buttons(x: f32, y: f32, held: &mut Vec<Button>) {
+    if y > STICK_THRESHOLD {
+        held.push(Button::StickUp);
+    }
+    if y < -STICK_THRESHOLD {
+        held.push(Button::StickDown);
+    }
+    if x < -STICK_THRESHOLD {
+        held.push(Button::StickLeft);
+    }
+    if x > STICK_THRESHOLD {
+        held.push(Button::StickRight);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use gilrs::{Axis, EventType, Gilrs};
+    use wasm_chip8::keymap::Button;
+
+    pub struct Backend {
+        gilrs: Gilrs,
+    }
+
+    impl Backend {
+        /// Returns `None` if gamepads aren't supported on this system.
+        pub fn new() -> Option<Self> {
+            Some(Self {
+                gilrs: Gilrs::new().ok()?,
+            })
+        }
+
+        pub fn hot_plug(&mut self) -> Option<String> {
+            let mut message = None;
+            // Also keeps the state of the gamepads up to date
+            while let Some(event) = self.gilrs.next_event() {
+                let name = self.gilrs.gamepad(event.id).name().to_string();
+                match event.event {
+                    EventType::Connected => message = Some(format!("Connected {}", name)),
+                    EventType::Disconnected => message = Some(format!("Disconnected {}", name)),
+                    _ => {}
+                }
+            }
+            message
+        }
+
+        pub fn held(&self) -> Vec<Button> {
+            let mut held = Vec::new();
+            for (_, gamepad) in self.gilrs.gamepads() {
+                for button in Button::ALL.iter() {
+                    let pressed = match gilrs_button(*button) {
+                        Some(gilrs) => gamepad.is_pressed(gilrs),
+                        None => false,
+                    };
+                    if pressed && !held.contains(button) {
+                        held.push(*button);
+                    }
+                }
+                super::stick_buttons(
+                    gamepad.value(Axis::LeftStickX),
+                    gamepad.value(Axis::LeftStickY),
+                    &mut held,
+                );
+            }
+            held
+        }
+    }
+
+    fn gilrs_button(button: Button) -> Option<gilrs::Button> {
+        Some(match button {
+            Button::DPadUp => gilrs::Button::DPadUp,
+            Button::DPadDown => gilrs::Button::DPadDown,
+            Button::DPadLeft => gilrs::Button::DPadLeft,
+            Button::DPadRight => gilrs::Button::DPadRight,
+            Button::South => gilrs::Button::South,
+            Button::East => gilrs::Button::East,
+            Button::West => gilrs::Button::West,
+            Button::North => gilrs::Button::North,
+            Button::LeftBumper => gilrs::Button::LeftTrigger,
+            Button::RightBumper => gilrs::Button::RightTrigger,
+            Button::LeftTrigger => gilrs::Button::LeftTrigger2,
+            Button::RightTrigger => gilrs::Button::RightTrigger2,
+            Button::Select => gilrs::Button::Select,
+            Button::Start => gilrs::Button::Start,
+            Button::StickUp | Button::StickDown | Button::StickLeft | Button::StickRight => {
+                return None
+            }
+        })
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod web {
+    use wasm_chip8::keymap::Button;
+
+    extern "C" {
+        fn chip8_gamepad_count() -> u32;
+        fn chip8_gamepad_buttons() -> u32;
+        fn chip8_gamepad_axis(axis: u32) -> f32;
+    }
+
+    pub struct Backend {
+        count: u32,
+    }
+
+    impl Backend {
+        pub fn new() -> Option<Self> {
+            Some(Self { count: 0 })
+        }
+
+        pub fn hot_plug(&mut self) -> Option<String> {
+            let count = unsafe { chip8_gamepad_count() };
+            let message = match count {
+                _ if count > self.count => Some("Gamepad connected".to_string()),
+                _ if count < self.count => Some("Gamepad disconnected".to_string()),
+                _ => None,
+            };
+            self.count = count;
+            message
+        }
+
+        pub fn held(&self) -> Vec<Button> {
+            // Bits in the order of `Button::ALL`, up to the stick directions
+            let bits = unsafe { chip8_gamepad_buttons() };
+            let mut held: Vec<Button> = Button::ALL
+                .iter()
+                .enumerate()
+                .filter(|(idx, _)| bits & (1 << idx) != 0)
+                .map(|(_, button)| *button)
+                .collect();
+            // The Gamepad API has y pointing down
+            let (x, y) = unsafe { (chip8_gamepad_axis(0), chip8_gamepad_axis(1)) };
+            super::stick_buttons(x, -y, &mut held);
+            held
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use crate::cpu::{Cpu, CpuError};
+use crate::scheduler::Scheduler;
+
+/// Drives a `Cpu` without any rendering or input, the same way the main loop does.
+pub struct Headless {
+    pub cpu: Cpu,
+    pub scheduler: Scheduler,
+}
+
+impl Headless {
+    pub fn new(rom: &[u8]) -> Result<Self, CpuError> {
+        let mut cpu = Cpu::new();
+        cpu.init_mem(rom)?;
+
+        Ok(Self {
+            cpu,
+            scheduler: Scheduler::default(),
+        })
+    }
+
+    pub fn run_frame(&mut self) -> Result<(), CpuError> {
+        self.scheduler.tick(&mut self.cpu)
+    }
+
+    pub fn run_frames(&mut self, frames: usize) -> Result<(), CpuError> {
+        for _ in 0..frames {
+            self.run_frame()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,313 @@
+use macroquad::prelude::{KeyCode, Rect};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// How the keys are arranged on the original hex keypad.
+pub const KEYPAD: [[usize; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+/// Host inputs bound to each of the 16 CHIP-8 keys.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bindings<T> {
+    pub keys: [Vec<T>; 16],
+}
+
+pub type Keymap = Bindings<KeyCode>;
+pub type PadMap = Bindings<Button>;
+
+/// Gamepad inputs, named after their position on the standard layout.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Button {
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+    South,
+    East,
+    West,
+    North,
+    LeftBumper,
+    RightBumper,
+    LeftTrigger,
+    RightTrigger,
+    Select,
+    Start,
+    /// The left stick pushed in a direction
+    StickUp,
+    StickDown,
+    StickLeft,
+    StickRight,
+}
+
+impl Button {
+    pub const ALL: [Button; 18] = [
+        Button::DPadUp,
+        Button::DPadDown,
+        Button::DPadLeft,
+        Button::DPadRight,
+        Button::South,
+        Button::East,
+        Button::West,
+        Button::North,
+        Button::LeftBumper,
+        Button::RightBumper,
+        Button::LeftTrigger,
+        Button::RightTrigger,
+        Button::Select,
+        Button::Start,
+        Button::StickUp,
+        Button::StickDown,
+        Button::StickLeft,
+        Button::StickRight,
+    ];
+}
+
+/// Host inputs that can be saved by name, e.g. `Q` or `DPadUp`.
+pub trait Named: Sized {
+    fn name(&self) -> String;
+    fn from_name(name: &str) -> Option<Self>;
+}
+
+impl Named for KeyCode {
+    fn name(&self) -> String {
+        format!("{:?}", self)
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        // Every key has a code below 512 on all platforms
+        (0..512)
+            .map(KeyCode::from)
+            .find(|code| *code != KeyCode::Unknown && code.name() == name)
+    }
+}
+
+impl Named for Button {
+    fn name(&self) -> String {
+        format!("{:?}", self)
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Button::ALL
+            .iter()
+            .copied()
+            .find(|button| button.name() == name)
+    }
+}
+
+/// Saved as a list of names for each CHIP-8 key.
+impl<T: Named> Serialize for Bindings<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let names: Vec<Vec<String>> = self
+            .keys
+            .iter()
+            .map(|bound| bound.iter().map(Named::name).collect())
+            .collect();
+        names.serialize(serializer)
+    }
+}
+
+/// Names that aren't known, e.g. from a newer version, are left out.
+impl<'de, T: Named> Deserialize<'de> for Bindings<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let names = <[Vec<String>; 16]>::deserialize(deserializer)?;
+        let mut bindings = Self {
+            keys: Default::default(),
+        };
+        for (bound, names) in bindings.keys.iter_mut().zip(names.iter()) {
+            bound.extend(names.iter().filter_map(|name| T::from_name(name)));
+        }
+        Ok(bindings)
+    }
+}
+
+// Listed by CHIP-8 key, 0 to F. The letter layouts keep the shape of the `KEYPAD` in the 1234 block
+pub const LAYOUTS: [(&str, [KeyCode; 16]); 5] = [
+    (
+        "QWERTY",
+        keypad_layout(KeyCode::Q, KeyCode::W, KeyCode::A, KeyCode::Z),
+    ),
+    (
+        "AZERTY",
+        keypad_layout(KeyCode::A, KeyCode::Z, KeyCode::Q, KeyCode::W),
+    ),
+    (
+        "QWERTZ",
+        keypad_layout(KeyCode::Q, KeyCode::W, KeyCode::A, KeyCode::Y),
+    ),
+    (
+        "Dvorak",
+        [
+            KeyCode::Q,
+            KeyCode::Key1,
+            KeyCode::Key2,
+            KeyCode::Key3,
+            KeyCode::Apostrophe,
+            KeyCode::Comma,
+            KeyCode::Period,
+            KeyCode::A,
+            KeyCode::O,
+            KeyCode::E,
+            KeyCode::Semicolon,
+            KeyCode::J,
+            KeyCode::Key4,
+            KeyCode::P,
+            KeyCode::U,
+            KeyCode::K,
+        ],
+    ),
+    // Digits where they are on the numpad, A to F on the keys around it
+    (
+        "Numpad hex",
+        [
+            KeyCode::Kp0,
+            KeyCode::Kp1,
+            KeyCode::Kp2,
+            KeyCode::Kp3,
+            KeyCode::Kp4,
+            KeyCode::Kp5,
+            KeyCode::Kp6,
+            KeyCode::Kp7,
+            KeyCode::Kp8,
+            KeyCode::Kp9,
+            KeyCode::KpDivide,
+            KeyCode::KpMultiply,
+            KeyCode::KpSubtract,
+            KeyCode::KpAdd,
+            KeyCode::KpEnter,
+            KeyCode::KpDecimal,
+        ],
+    ),
+];
+
+/// The QWERTY-style layouts only differ in where four of the letters are: the first two on the
+/// top letter row, the one starting the home row and the one starting the bottom row.
+const fn keypad_layout(q: KeyCode, w: KeyCode, a: KeyCode, z: KeyCode) -> [KeyCode; 16] {
+    [
+        KeyCode::X,
+        KeyCode::Key1,
+        KeyCode::Key2,
+        KeyCode::Key3,
+        q,
+        w,
+        KeyCode::E,
+        a,
+        KeyCode::S,
+        KeyCode::D,
+        z,
+        KeyCode::C,
+        KeyCode::Key4,
+        KeyCode::R,
+        KeyCode::F,
+        KeyCode::V,
+    ]
+}
+
+/// QWERTY, with Y on A as well so QWERTZ keyboards work out of the box like they always have.
+impl Default for Keymap {
+    fn default() -> Self {
+        let mut keymap = Self::layout(LAYOUTS[0].0).unwrap();
+        keymap.keys[0xA].push(KeyCode::Y);
+        keymap
+    }
+}
+
+/// Both the D-pad and the stick on 2, 4, 6 and 8, the usual directions in CHIP-8 games, and the
+/// bottom face button on 5.
+impl Default for PadMap {
+    fn default() -> Self {
+        let mut padmap = Self {
+            keys: Default::default(),
+        };
+        padmap.keys[0x2] = vec![Button::DPadUp, Button::StickUp];
+        padmap.keys[0x4] = vec![Button::DPadLeft, Button::StickLeft];
+        padmap.keys[0x6] = vec![Button::DPadRight, Button::StickRight];
+        padmap.keys[0x8] = vec![Button::DPadDown, Button::StickDown];
+        padmap.keys[0x5] = vec![Button::South];
+        padmap
+    }
+}
+
+impl Keymap {
+    /// One of the built-in `LAYOUTS`, by name.
+    pub fn layout(name: &str) -> Option<Self> {
+        let (_, keys) = LAYOUTS.iter().find(|(layout, _)| *layout == name)?;
+        let mut keymap = Self {
+            keys: Default::default(),
+        };
+        for (bound, host) in keymap.keys.iter_mut().zip(keys.iter()) {
+            bound.push(*host);
+        }
+        Some(keymap)
+    }
+}
+
+impl<T: Copy + PartialEq> Bindings<T> {
+    /// Binds `host` to `key`, taking it away from whatever it was bound to before.
+    pub fn bind(&mut self, key: usize, host: T) {
+        for bound in self.keys.iter_mut() {
+            bound.retain(|bound| *bound != host);
+        }
+        self.keys[key].push(host);
+    }
+
+    pub fn unbind(&mut self, key: usize, host: T) {
+        self.keys[key].retain(|bound| *bound != host);
+    }
+
+    /// Whether any CHIP-8 key is bound to `host`.
+    pub fn is_bound(&self, host: T) -> bool {
+        self.keys.iter().any(|bound| bound.contains(&host))
+    }
+
+    /// Which CHIP-8 keys are held, given which host keys are.
+    pub fn pressed(&self, is_down: impl Fn(T) -> bool) -> [bool; 16] {
+        let mut pressed = [false; 16];
+        for (pressed, bound) in pressed.iter_mut().zip(self.keys.iter()) {
+            *pressed = bound.iter().any(|host| is_down(*host));
+        }
+        pressed
+    }
+}
+
+/// Lays out buttons for an on-screen keypad in `area`, as large as fits. Only keys in `shown` get
+/// a button, or all of them if it's `None`. Rows and columns without any are left out, the rest
+/// keep their place on the hex keypad.
+pub fn keypad_buttons(area: Rect, shown: Option<&[usize]>) -> Vec<(usize, Rect)> {
+    let is_shown = |key: &usize| shown.is_none_or(|shown| shown.contains(key));
+    let rows: Vec<&[usize; 4]> = KEYPAD
+        .iter()
+        .filter(|row| row.iter().any(is_shown))
+        .collect();
+    let columns: Vec<usize> = (0..4)
+        .filter(|column| KEYPAD.iter().any(|row| is_shown(&row[*column])))
+        .collect();
+    if rows.is_empty() {
+        return Vec::new();
+    }
+
+    let size = (area.w / columns.len() as f32).min(area.h / rows.len() as f32);
+    let x = area.x + (area.w - size * columns.len() as f32) / 2.0;
+    let y = area.y + (area.h - size * rows.len() as f32) / 2.0;
+    // Gap between buttons
+    let gap = size * 0.05;
+
+    let mut buttons = Vec::new();
+    for (row_idx, row) in rows.iter().enumerate() {
+        for (column_idx, column) in columns.iter().enumerate() {
+            let key = row[*column];
+            if is_shown(&key) {
+                let rect = Rect::new(
+                    x + column_idx as f32 * size + gap,
+                    y + row_idx as f32 * size + gap,
+                    size - 2.0 * gap,
+                    size - 2.0 * gap,
+                );
+                buttons.push((key, rect));
+            }
+        }
+    }
+    buttons
+}
@@ -0,0 +1,10 @@
+pub mod audio;
+pub mod capture;
+pub mod cpu;
+pub mod disassembler;
+pub mod display;
+pub mod headless;
+pub mod keymap;
+pub mod movie;
+pub mod roms;
+pub mod scheduler;
@@ -1,10 +1,25 @@
-use crate::cpu::Cpu;
-use crate::ui::{show_menu, DebuggerState, MenuState};
+use crate::gamepad::Gamepads;
+use crate::shaders::Pipeline;
+use crate::sound::AudioOut;
+use crate::ui::{
+    show_menu, DebuggerState, MenuState, MovieMode, RecordingFormat, ScreenshotMode, TouchKeypad,
+};
+use macroquad::miniquad::date;
 use macroquad::prelude::*;
+use wasm_chip8::audio::{encode_wav, Beeper, DEFAULT_SAMPLE_RATE};
+use wasm_chip8::capture::{encode_png, flip_vertical, scale_image, GifRecorder};
+use wasm_chip8::cpu::Cpu;
+use wasm_chip8::display::{fb_to_img, viewport, Persistence, Phosphor};
+use wasm_chip8::keymap::{keypad_buttons, Keymap};
+use wasm_chip8::movie::{Movie, MovieState};
+use wasm_chip8::roms;
+use wasm_chip8::scheduler::Scheduler;
 
-mod cpu;
-mod disassembler;
-mod roms;
+mod export;
+mod gamepad;
+mod settings;
+mod shaders;
+mod sound;
 mod ui;
 
 #[derive(PartialEq)]
@@ -13,14 +28,60 @@ pub enum State {
     InGame(String),
 }
 
+// How long messages like "Saved ..." stay on screen, in seconds
+const NOTICE_SECONDS: f64 = 3.0;
+
+// Part of the screen height the touch keypad takes up below the display
+const KEYPAD_SHARE: f32 = 0.4;
+
+// Wall time an uncapped turbo frame may spend emulating, leaving room for rendering
+const UNCAPPED_BUDGET: f64 = 0.012;
+
+enum VideoRecording {
+    Gif(GifRecorder),
+    /// Numbered PNG files written to `dir` as the frames come in
+    PngSequence {
+        dir: String,
+        frames: usize,
+    },
+}
+
+/// Playback speed controlled by the in-game hotkeys.
+struct Speed {
+    paused: bool,
+    slow_motion: f64,
+}
+
+impl Default for Speed {
+    fn default() -> Self {
+        Self {
+            paused: false,
+            slow_motion: 1.0,
+        }
+    }
+}
+
+impl Speed {
+    fn next_slow_motion(&mut self) {
+        self.slow_motion = match self.slow_motion {
+            x if x > 0.5 => 0.5,
+            x if x > 0.25 => 0.25,
+            _ => 1.0,
+        };
+    }
+}
+
 #[macroquad::main("CHIP-8 EMU")]
 async fn main() {
-    let material = load_material(
-        include_str!("CRT_shader.vert"),
-        include_str!("CRT_shader.frag"),
-        MaterialParams::default(),
-    )
-    .unwrap();
+    let mut notice: Option<(String, f64)> = None;
+    let mut menu_state = settings::load().unwrap_or_else(|err| {
+        notice = Some((err, get_time()));
+        MenuState::default()
+    });
+    let mut settings_changed = false;
+    let mut border = menu_state.border;
+    let mut pipeline = Pipeline::new(target_size(border));
+    let mut fullscreen = false;
 
     let mut buffer = Image {
         width: 64,
@@ -31,129 +92,496 @@ async fn main() {
     let texture = Texture2D::from_image(&buffer);
     texture.set_filter(FilterMode::Nearest);
 
-    let target = render_target(68, 36);
-    target.texture.set_filter(FilterMode::Nearest);
+    let mut target = border_target(border);
 
     let mut cpu = Cpu::new();
+    let mut scheduler = Scheduler::default();
+    let mut speed = Speed::default();
+    let mut audio_out = AudioOut::new();
+    let sample_rate = audio_out
+        .as_ref()
+        .map_or(DEFAULT_SAMPLE_RATE, |out| out.sample_rate());
+    let mut wav_recording: Option<Vec<f32>> = None;
+    let mut video_recording: Option<VideoRecording> = None;
+    let mut gamepads = Gamepads::new();
+    // The touch keypad shows up by itself once the screen has been touched
+    let mut touch_seen = false;
 
     let mut state = State::Menu;
-    let mut menu_state = MenuState::default();
+    let mut phosphor = Phosphor::new(menu_state.persistence, menu_state.decay);
     let mut debugger_state = DebuggerState::default();
 
     loop {
+        if let Some(message) = gamepads.update() {
+            notice = Some((message, get_time()));
+        }
+        if is_key_pressed(KeyCode::F11) {
+            menu_state.fullscreen = !menu_state.fullscreen;
+            settings_changed = true;
+        }
+        if menu_state.fullscreen != fullscreen {
+            fullscreen = menu_state.fullscreen;
+            set_fullscreen(fullscreen);
+        }
+
         if state == State::Menu {
-            show_menu(&mut state, &mut menu_state);
+            settings_changed |= show_menu(&mut state, &mut menu_state, &gamepads);
             egui_macroquad::draw();
             if let State::InGame(rom) = &state {
-                cpu = Cpu::new();
-                cpu.init_mem(&roms::get_bytes(rom));
-                cpu.st_compat = menu_state.load_store_compat;
-                cpu.sh_compat = menu_state.shift_compat;
+                if debugger_state.error.take().is_some() {
+                    debugger_state.running = true;
+                }
+                match start_game(rom, &menu_state) {
+                    Ok((rom, new_cpu, new_scheduler)) => {
+                        state = State::InGame(rom);
+                        cpu = new_cpu;
+                        scheduler = new_scheduler;
+                    }
+                    Err(err) => {
+                        notice = Some((err, get_time()));
+                        state = State::Menu;
+                    }
+                }
+                // Rendered even without an audio device, so it can still be recorded
+                let mut beeper = Beeper::new(sample_rate);
+                beeper.waveform = menu_state.waveform;
+                beeper.frequency = menu_state.frequency;
+                beeper.volume = menu_state.volume;
+                beeper.muted = menu_state.muted;
+                scheduler.audio = Some(beeper);
+                speed = Speed::default();
+                phosphor = Phosphor::new(menu_state.persistence, menu_state.decay);
+                if menu_state.border != border {
+                    border = menu_state.border;
+                    target.texture.delete();
+                    target = border_target(border);
+                    pipeline.resize(target_size(border));
+                }
             }
         } else {
+            let rom = match &state {
+                State::InGame(rom) => rom.clone(),
+                State::Menu => unreachable!(),
+            };
+
+            let controls = menu_state.controls(&rom).clone();
+            let keymap = &controls.keys;
+
             if is_key_pressed(KeyCode::Escape) {
                 state = State::Menu;
             }
 
+            // Leaving the game also ends any recording
+            if hotkey_pressed(keymap, KeyCode::F9) || state == State::Menu {
+                if let Some(samples) = wav_recording.take() {
+                    let path = format!("recordings/{}", export::file_name(&rom, "wav"));
+                    let message = match export::save_file(&path, &encode_wav(&samples, sample_rate))
+                    {
+                        Ok(path) => format!("Saved {}", path),
+                        Err(err) => err,
+                    };
+                    notice = Some((message, get_time()));
+                } else if state != State::Menu {
+                    wav_recording = Some(Vec::new());
+                }
+            }
+            if hotkey_pressed(keymap, KeyCode::F10) || state == State::Menu {
+                if let Some(recording) = video_recording.take() {
+                    let message = match recording {
+                        VideoRecording::Gif(gif) => {
+                            let path = format!("recordings/{}", export::file_name(&rom, "gif"));
+                            match export::save_file(&path, &gif.finish()) {
+                                Ok(path) => format!("Saved {}", path),
+                                Err(err) => err,
+                            }
+                        }
+                        VideoRecording::PngSequence { dir, frames } => {
+                            format!("Saved {} frames to {}", frames, dir)
+                        }
+                    };
+                    notice = Some((message, get_time()));
+                    scheduler.record_frames = false;
+                } else if state != State::Menu {
+                    video_recording = Some(match menu_state.recording_format {
+                        RecordingFormat::Gif => VideoRecording::Gif(GifRecorder::new(
+                            &menu_state.palette(),
+                            menu_state.recording_scale,
+                        )),
+                        RecordingFormat::PngSequence => VideoRecording::PngSequence {
+                            dir: format!("recordings/{}", export::file_name(&rom, "frames")),
+                            frames: 0,
+                        },
+                    });
+                    scheduler.record_frames = true;
+                }
+            }
+
+            if state == State::Menu {
+                if let Some(MovieState::Recording(movie)) = scheduler.movie.take() {
+                    let path = format!("movies/{}", export::file_name(&rom, "c8m"));
+                    let message = match export::save_file(&path, movie.to_text().as_bytes()) {
+                        Ok(path) => format!("Saved {}", path),
+                        Err(err) => err,
+                    };
+                    notice = Some((message, get_time()));
+                }
+            }
+
+            if hotkey_pressed(keymap, KeyCode::P) {
+                speed.paused = !speed.paused;
+            }
+            if hotkey_pressed(keymap, KeyCode::F5) {
+                speed.next_slow_motion();
+            }
+            let turbo = is_key_down(KeyCode::Tab) && !keymap.is_bound(KeyCode::Tab);
+
+            let mut frames = 0;
             if debugger_state.running {
-                // At 60fps, this basically results in a CPU speed of 480Hz
-                for _ in 0..8 {
-                    cpu.step();
+                let result = if speed.paused {
+                    if hotkey_pressed(keymap, KeyCode::N) {
+                        scheduler.tick(&mut cpu).map(|_| 1)
+                    } else {
+                        Ok(0)
+                    }
+                } else if turbo && menu_state.turbo_uncapped {
+                    let start = get_time();
+                    let mut ticks = 0;
+                    let mut result = Ok(());
+                    while result.is_ok() && get_time() - start < UNCAPPED_BUDGET {
+                        result = scheduler.tick(&mut cpu);
+                        ticks += 1;
+                    }
+                    result.map(|_| ticks)
+                } else {
+                    let factor = if turbo {
+                        f64::from(menu_state.turbo)
+                    } else {
+                        speed.slow_motion
+                    };
+                    scheduler.update(&mut cpu, f64::from(get_frame_time()), factor)
+                };
+
+                match result {
+                    Ok(ticks) => frames = ticks as u32,
+                    Err(err) => {
+                        debugger_state.halt(err);
+                        menu_state.show_debugger = true;
+                        settings_changed = true;
+                    }
                 }
-                cpu.dec_regs();
             }
 
-            process_input(&mut cpu);
+            if scheduler.movie.as_ref().is_some_and(MovieState::finished) {
+                let message = match scheduler.movie.take().and_then(|movie| movie.desync()) {
+                    Some(frame) => format!("Movie ended, out of sync since frame {}", frame),
+                    None => "Movie ended in sync".to_string(),
+                };
+                notice = Some((message, get_time()));
+            }
 
-            fb_to_img(&mut buffer, &cpu.get_framebuffer(), menu_state.alpha);
+            let samples = scheduler.take_samples();
+            if let Some(out) = &mut audio_out {
+                out.push(&samples);
+            }
+            if let Some(recording) = &mut wav_recording {
+                recording.extend(samples);
+            }
+            for fb in scheduler.take_frames() {
+                match &mut video_recording {
+                    Some(VideoRecording::Gif(gif)) => gif.push(&fb),
+                    Some(VideoRecording::PngSequence { dir, frames }) => {
+                        let img = scale_image(
+                            &framebuffer_image(&fb, &menu_state),
+                            menu_state.recording_scale,
+                        );
+                        let path = format!("{}/{:05}.png", dir, frames);
+                        if let Err(err) = export::save_file(&path, &encode_png(&img)) {
+                            notice = Some((err, get_time()));
+                            video_recording = None;
+                            scheduler.record_frames = false;
+                            break;
+                        }
+                        *frames += 1;
+                    }
+                    None => {}
+                }
+            }
+
+            let mut points: Vec<Vec2> = touches()
+                .iter()
+                .filter(|touch| !matches!(touch.phase, TouchPhase::Ended | TouchPhase::Cancelled))
+                .map(|touch| touch.position)
+                .collect();
+            touch_seen |= !points.is_empty();
+            if is_mouse_button_down(MouseButton::Left) {
+                points.push(mouse_position().into());
+            }
+
+            let screen = vec2(screen_width(), screen_height());
+            let keypad = match menu_state.touch_keypad {
+                TouchKeypad::Auto if touch_seen => TouchKeypad::Below,
+                TouchKeypad::Auto => TouchKeypad::Off,
+                keypad => keypad,
+            };
+            let (display_area, keypad_area) = match keypad {
+                TouchKeypad::Below => {
+                    let height = (screen.y * KEYPAD_SHARE).min(screen.x);
+                    let area = Rect::new(0.0, screen.y - height, screen.x, height);
+                    (vec2(screen.x, screen.y - height), Some(area))
+                }
+                TouchKeypad::Overlay => (screen, Some(Rect::new(0.0, 0.0, screen.x, screen.y))),
+                TouchKeypad::Auto | TouchKeypad::Off => (screen, None),
+            };
+            let shown = if menu_state.hide_unused_keys {
+                roms::used_keys(&rom)
+            } else {
+                None
+            };
+            let keypad_buttons =
+                keypad_area.map_or_else(Vec::new, |area| keypad_buttons(area, shown));
+            let mut touched = [false; 16];
+            for (key, rect) in keypad_buttons.iter() {
+                touched[*key] = points.iter().any(|point| rect.contains(*point));
+            }
+
+            let keys = keymap.pressed(is_key_down);
+            let buttons = controls.pad.pressed(|button| gamepads.is_down(button));
+            for key in 0..16 {
+                cpu.set_key(key, keys[key] || buttons[key] || touched[key]);
+            }
+
+            fb_to_img(
+                &mut buffer,
+                cpu.get_framebuffer(),
+                scheduler.previous_frame(),
+                &mut phosphor,
+                &menu_state.palette(),
+                frames,
+            );
             texture.update(&buffer);
 
+            let size = target_size(border);
             set_camera(&Camera2D {
                 render_target: Some(target),
-                ..Camera2D::from_display_rect(Rect::new(0.0, 0.0, 68.0, 36.0))
+                ..Camera2D::from_display_rect(Rect::new(0.0, 0.0, size.x, size.y))
             });
 
-            draw_texture(texture, 2.0, 2.0, WHITE);
+            draw_texture(texture, border as f32, border as f32, WHITE);
 
             set_default_camera();
 
-            if menu_state.crt_shader {
-                gl_use_material(material);
-            }
-
-            draw_texture_ex(
+            let dest = viewport(menu_state.scale_mode, size, display_area);
+            pipeline.draw(
                 target.texture,
-                0.0,
-                0.0,
-                WHITE,
-                DrawTextureParams {
-                    dest_size: Some(vec2(get_dims().0, get_dims().1)),
-                    flip_y: true,
-                    ..Default::default()
-                },
+                menu_state.shader_effects(),
+                &menu_state.shader_params,
+                dest,
             );
 
-            gl_use_default_material();
+            // Taken before anything is drawn on top of the emulator
+            if hotkey_pressed(keymap, KeyCode::F12) || debugger_state.screenshot {
+                debugger_state.screenshot = false;
+                let img = screenshot(&cpu, &menu_state, dest);
+                let path = format!("screenshots/{}", export::file_name(&rom, "png"));
+                let message = match export::save_file(&path, &encode_png(&img)) {
+                    Ok(path) => format!("Saved {}", path),
+                    Err(err) => err,
+                };
+                notice = Some((message, get_time()));
+            }
+
+            draw_keypad(&keypad_buttons, &touched);
+
+            let indicator = if speed.paused {
+                Some("PAUSED".to_string())
+            } else if turbo && menu_state.turbo_uncapped {
+                Some(">> MAX".to_string())
+            } else if turbo {
+                Some(format!(">> {}x", menu_state.turbo))
+            } else if speed.slow_motion < 1.0 {
+                Some(format!("{}x", speed.slow_motion))
+            } else {
+                None
+            };
+            if let Some(text) = indicator {
+                draw_text(&text, 8.0, 24.0, 24.0, YELLOW);
+            }
+            let recording_movie = matches!(scheduler.movie, Some(MovieState::Recording(_)));
+            if wav_recording.is_some() || video_recording.is_some() || recording_movie {
+                draw_text("REC", screen_width() - 56.0, 24.0, 24.0, RED);
+            }
+            if let Some(movie @ MovieState::Playing { .. }) = &scheduler.movie {
+                let text = format!("PLAY {}/{}", movie.frame(), movie.movie().frames.len());
+                let width = measure_text(&text, None, 24, 1.0).width;
+                draw_text(&text, screen_width() - width - 8.0, 24.0, 24.0, GREEN);
+                if let Some(frame) = movie.desync() {
+                    let text = format!("DESYNC AT {}", frame);
+                    let width = measure_text(&text, None, 24, 1.0).width;
+                    draw_text(&text, screen_width() - width - 8.0, 48.0, 24.0, RED);
+                }
+            }
 
             if menu_state.show_debugger {
-                ui::show_debugger(&mut debugger_state, &mut cpu);
+                ui::show_debugger(&mut debugger_state, &mut cpu, scheduler.movie.is_some());
                 egui_macroquad::draw();
             }
         }
+
+        // Saved right away, in the menu or in game. There's no telling when the window or tab gets
+        // closed, and no hook for it
+        if settings_changed {
+            settings_changed = false;
+            if let Err(err) = settings::to_text(&menu_state).and_then(|text| settings::save(&text))
+            {
+                notice = Some((err, get_time()));
+            }
+        }
+
+        if let Some((message, shown)) = &notice {
+            if get_time() - shown < NOTICE_SECONDS {
+                draw_text(message, 8.0, screen_height() - 12.0, 24.0, YELLOW);
+            } else {
+                notice = None;
+            }
+        }
+
         next_frame().await
     }
 }
 
-fn get_dims() -> (f32, f32) {
-    if screen_width() / 2.0 > screen_height() {
-        (screen_height() * 2.0, screen_height())
-    } else {
-        (screen_width(), screen_width() / 2.0)
+/// Sets up a game of `rom`, or of the movie to be played back, recording it if asked to. Returns
+/// the ROM actually started.
+fn start_game(rom: &str, menu_state: &MenuState) -> Result<(String, Cpu, Scheduler), String> {
+    let movie = match menu_state.movie_mode {
+        MovieMode::Play => load_movie(&menu_state.movie_path)?,
+        MovieMode::Off | MovieMode::Record => {
+            let seed = (date::now() * 1000.0) as u64;
+            let mut movie = Movie::new(rom, &roms::get_bytes(rom), seed, menu_state.ips);
+            movie.st_compat = menu_state.load_store_compat;
+            movie.sh_compat = menu_state.shift_compat;
+            movie.vip_timing = menu_state.vip_timing;
+            movie.display_wait = menu_state.display_wait;
+            movie
+        }
+    };
+    let (cpu, mut scheduler) = movie
+        .start(&roms::get_bytes(&movie.rom))
+        .map_err(|err| err.to_string())?;
+    let rom = movie.rom.clone();
+    scheduler.movie = match menu_state.movie_mode {
+        MovieMode::Off => None,
+        MovieMode::Record => Some(MovieState::Recording(movie)),
+        MovieMode::Play => Some(MovieState::play(movie)),
+    };
+    Ok((rom, cpu, scheduler))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn load_movie(path: &str) -> Result<Movie, String> {
+    let text =
+        std::fs::read_to_string(path).map_err(|err| format!("Can't read {}: {}", path, err))?;
+    let movie = Movie::parse(&text).map_err(|err| format!("{}: {}", path, err))?;
+    if !roms::ROMS.contains(&movie.rom.as_str()) {
+        return Err(format!(
+            "{} is for {}, which isn't built in",
+            path, movie.rom
+        ));
     }
+    Ok(movie)
 }
 
-fn process_input(cpu: &mut Cpu) {
-    let key_codes = [
-        KeyCode::X,
-        KeyCode::Key1,
-        KeyCode::Key2,
-        KeyCode::Key3,
-        KeyCode::Q,
-        KeyCode::W,
-        KeyCode::E,
-        KeyCode::A,
-        KeyCode::S,
-        KeyCode::D,
-        KeyCode::Z,
-        KeyCode::C,
-        KeyCode::Key4,
-        KeyCode::R,
-        KeyCode::F,
-        KeyCode::V,
-    ];
-
-    for (idx, code) in key_codes.iter().enumerate() {
-        cpu.set_key(idx, is_key_down(*code));
-
-        // Special case for QWERTZ keyboards
-        if *code == KeyCode::Z && is_key_down(KeyCode::Y) {
-            cpu.set_key(idx, true);
-        }
+#[cfg(target_arch = "wasm32")]
+fn load_movie(_path: &str) -> Result<Movie, String> {
+    unreachable!("Movies can't be played back in the browser")
+}
+
+/// `viewport` is where the emulator is drawn on screen.
+fn screenshot(cpu: &Cpu, menu_state: &MenuState, viewport: Rect) -> Image {
+    if menu_state.screenshot_mode == ScreenshotMode::Screen {
+        let mut screen = get_screen_data();
+        flip_vertical(&mut screen);
+        // Integer scaling crops the image in windows that are too small for it
+        let bounds = Rect::new(0.0, 0.0, screen.width as f32, screen.height as f32);
+        return match viewport.intersect(bounds) {
+            Some(rect) => screen.sub_image(rect),
+            None => screen,
+        };
+    }
+
+    let img = framebuffer_image(cpu.get_framebuffer(), menu_state);
+    match menu_state.screenshot_mode {
+        ScreenshotMode::Scaled => scale_image(&img, menu_state.screenshot_scale),
+        _ => img,
     }
 }
 
-fn fb_to_img(img: &mut Image, fb: &[bool; 32 * 64], alpha: u8) {
-    for y in 0..32 {
-        for x in 0..64 {
-            img.set_pixel(
-                x,
-                y,
-                if fb[y as usize * 64 + x as usize] {
-                    WHITE
-                } else {
-                    Color::from_rgba(0, 0, 0, alpha)
-                },
-            )
-        }
+/// `fb` in the active palette. Without persistence, so the image shows exactly what is in it.
+fn framebuffer_image(fb: &[bool; 32 * 64], menu_state: &MenuState) -> Image {
+    let mut img = Image::gen_image_color(64, 32, BLACK);
+    fb_to_img(
+        &mut img,
+        fb,
+        fb,
+        &mut Phosphor::new(Persistence::Off, 0.0),
+        &menu_state.palette(),
+        1,
+    );
+    img
+}
+
+/// Size of the render target the framebuffer is drawn into, `border` pixels in on every side.
+fn target_size(border: u32) -> Vec2 {
+    vec2(64.0, 32.0) + Vec2::splat(2.0 * border as f32)
+}
+
+fn border_target(border: u32) -> RenderTarget {
+    let size = target_size(border);
+    let target = render_target(size.x as u32, size.y as u32);
+    target.texture.set_filter(FilterMode::Nearest);
+    target
+}
+
+/// Only does anything on Windows and in the browser, miniquad doesn't support it elsewhere yet.
+fn set_fullscreen(fullscreen: bool) {
+    unsafe { get_internal_gl() }
+        .quad_context
+        .set_fullscreen(fullscreen);
+}
+
+fn draw_keypad(buttons: &[(usize, Rect)], touched: &[bool; 16]) {
+    for (key, rect) in buttons {
+        // Translucent, so it can go over the display
+        let alpha = if touched[*key] { 0.5 } else { 0.2 };
+        draw_rectangle(
+            rect.x,
+            rect.y,
+            rect.w,
+            rect.h,
+            Color::new(1.0, 1.0, 1.0, alpha),
+        );
+        draw_rectangle_lines(
+            rect.x,
+            rect.y,
+            rect.w,
+            rect.h,
+            2.0,
+            Color::new(1.0, 1.0, 1.0, 0.6),
+        );
+
+        let label = format!("{:X}", key);
+        let font_size = (rect.h * 0.5) as u16;
+        let dims = measure_text(&label, None, font_size, 1.0);
+        draw_text(
+            &label,
+            rect.x + (rect.w - dims.width) / 2.0,
+            rect.y + (rect.h + dims.height) / 2.0,
+            f32::from(font_size),
+            Color::new(1.0, 1.0, 1.0, 0.8),
+        );
     }
 }
+
+/// Hotkeys give way to keys the ROM's bindings use.
+fn hotkey_pressed(keymap: &Keymap, code: KeyCode) -> bool {
+    is_key_pressed(code) && !keymap.is_bound(code)
+}
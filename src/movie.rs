@@ -0,0 +1,291 @@
+//! Input movies: the keypad state of every frame, together with everything else a run depends on,
+//! so it can be played back exactly. Checksums of the machine state along the way show where a
+//! playback stopped matching the recording.
+
+use std::fmt;
+
+use crate::cpu::{Cpu, CpuError};
+use crate::scheduler::Scheduler;
+
+/// Frames between state checksums.
+pub const CHECKSUM_INTERVAL: usize = 60;
+
+const MAGIC: &str = "CHIP-8 movie 1";
+
+/// The keypad state of one frame, one bit per key, and sometimes the machine state at its end.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frame {
+    pub keys: u16,
+    pub checksum: Option<u32>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Movie {
+    pub rom: String,
+    /// To make sure it's played back with the same ROM
+    pub rom_checksum: u32,
+    pub seed: u64,
+    pub ips: u32,
+    pub st_compat: bool,
+    pub sh_compat: bool,
+    pub vip_timing: bool,
+    pub display_wait: bool,
+    pub frames: Vec<Frame>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MovieError {
+    /// Line that isn't part of a movie file, counting from 1
+    Format(usize),
+    RomMismatch,
+    Cpu(CpuError),
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MovieError::Format(line) => write!(f, "Not a valid movie file, see line {}", line),
+            MovieError::RomMismatch => write!(f, "The movie was recorded with a different ROM"),
+            MovieError::Cpu(err) => err.fmt(f),
+        }
+    }
+}
+
+impl From<CpuError> for MovieError {
+    fn from(err: CpuError) -> Self {
+        MovieError::Cpu(err)
+    }
+}
+
+/// FNV-1a, like `Cpu::checksum`.
+fn rom_checksum(rom: &[u8]) -> u32 {
+    rom.iter().fold(0x811C_9DC5, |hash, byte| {
+        (hash ^ u32::from(*byte)).wrapping_mul(0x0100_0193)
+    })
+}
+
+impl Movie {
+    /// An empty movie of `rom`, with all quirks off.
+    pub fn new(name: &str, rom: &[u8], seed: u64, ips: u32) -> Self {
+        Self {
+            rom: name.to_string(),
+            rom_checksum: rom_checksum(rom),
+            seed,
+            ips,
+            st_compat: false,
+            sh_compat: false,
+            vip_timing: false,
+            display_wait: false,
+            frames: Vec::new(),
+        }
+    }
+
+    /// A CPU and scheduler set up the way the movie starts, to be driven one `Scheduler::tick` per
+    /// frame. Neither has the movie attached yet.
+    pub fn start(&self, rom: &[u8]) -> Result<(Cpu, Scheduler), MovieError> {
+        if rom_checksum(rom) != self.rom_checksum {
+            return Err(MovieError::RomMismatch);
+        }
+        let mut cpu = Cpu::new();
+        cpu.init_mem(rom)?;
+        cpu.seed_rng(self.seed);
+        cpu.st_compat = self.st_compat;
+        cpu.sh_compat = self.sh_compat;
+        cpu.vip_timing = self.vip_timing;
+        cpu.display_wait = self.display_wait;
+        Ok((cpu, Scheduler::new(self.ips)))
+    }
+
+    /// The text format, a header followed by one line per frame: the keys as a hex bitmask and
+    /// possibly a checksum.
+    pub fn to_text(&self) -> String {
+        let quirks = [
+            (self.st_compat, " load-store"),
+            (self.sh_compat, " shift"),
+            (self.vip_timing, " vip-timing"),
+            (self.display_wait, " display-wait"),
+        ];
+        let mut text = format!(
+            "{}\nrom {}\nrom-checksum {:08x}\nseed {:016x}\nips {}\nquirks",
+            MAGIC, self.rom, self.rom_checksum, self.seed, self.ips
+        );
+        for (enabled, name) in quirks.iter() {
+            if *enabled {
+                text.push_str(name);
+            }
+        }
+        text.push_str("\nframes\n");
+        for frame in self.frames.iter() {
+            match frame.checksum {
+                Some(checksum) => text.push_str(&format!("{:04x} {:08x}\n", frame.keys, checksum)),
+                None => text.push_str(&format!("{:04x}\n", frame.keys)),
+            }
+        }
+        text
+    }
+
+    pub fn parse(text: &str) -> Result<Self, MovieError> {
+        let mut lines = text.lines();
+        let mut header_lines = 0;
+        let mut next = |key: &str| {
+            header_lines += 1;
+            lines
+                .next()
+                .and_then(|line| line.strip_prefix(key))
+                .map(|value| value.trim().to_string())
+                .ok_or(MovieError::Format(header_lines))
+        };
+
+        next(MAGIC)?;
+        let rom = next("rom ")?;
+        let rom_checksum =
+            u32::from_str_radix(&next("rom-checksum ")?, 16).map_err(|_| MovieError::Format(3))?;
+        let seed = u64::from_str_radix(&next("seed ")?, 16).map_err(|_| MovieError::Format(4))?;
+        let ips = next("ips ")?.parse().map_err(|_| MovieError::Format(5))?;
+        let mut movie = Self {
+            rom,
+            rom_checksum,
+            seed,
+            ips,
+            st_compat: false,
+            sh_compat: false,
+            vip_timing: false,
+            display_wait: false,
+            frames: Vec::new(),
+        };
+        for quirk in next("quirks")?.split_whitespace() {
+            match quirk {
+                "load-store" => movie.st_compat = true,
+                "shift" => movie.sh_compat = true,
+                "vip-timing" => movie.vip_timing = true,
+                "display-wait" => movie.display_wait = true,
+                _ => return Err(MovieError::Format(6)),
+            }
+        }
+        next("frames")?;
+
+        for (idx, line) in lines.enumerate() {
+            let mut fields = line.split_whitespace();
+            let keys = fields.next().map(|keys| u16::from_str_radix(keys, 16));
+            let checksum = fields
+                .next()
+                .map(|checksum| u32::from_str_radix(checksum, 16));
+            let frame = match (keys, checksum, fields.next()) {
+                (Some(Ok(keys)), None, None) => Frame {
+                    keys,
+                    checksum: None,
+                },
+                (Some(Ok(keys)), Some(Ok(checksum)), None) => Frame {
+                    keys,
+                    checksum: Some(checksum),
+                },
+                _ => return Err(MovieError::Format(header_lines + idx + 1)),
+            };
+            movie.frames.push(frame);
+        }
+        Ok(movie)
+    }
+}
+
+/// A movie being recorded or played back, see `Scheduler::movie`.
+pub enum MovieState {
+    Recording(Movie),
+    Playing {
+        movie: Movie,
+        /// The next frame to be played
+        frame: usize,
+        /// The first frame whose checksum didn't match
+        desync: Option<usize>,
+    },
+}
+
+impl MovieState {
+    pub fn play(movie: Movie) -> Self {
+        MovieState::Playing {
+            movie,
+            frame: 0,
+            desync: None,
+        }
+    }
+
+    pub fn movie(&self) -> &Movie {
+        match self {
+            MovieState::Recording(movie) | MovieState::Playing { movie, .. } => movie,
+        }
+    }
+
+    /// Frames recorded or played so far.
+    pub fn frame(&self) -> usize {
+        match self {
+            MovieState::Recording(movie) => movie.frames.len(),
+            MovieState::Playing { frame, .. } => *frame,
+        }
+    }
+
+    pub fn desync(&self) -> Option<usize> {
+        match self {
+            MovieState::Recording(_) => None,
+            MovieState::Playing { desync, .. } => *desync,
+        }
+    }
+
+    /// Whether playback has run out of frames. The keypad is left to the player from then on.
+    pub fn finished(&self) -> bool {
+        match self {
+            MovieState::Recording(_) => false,
+            MovieState::Playing { movie, frame, .. } => *frame >= movie.frames.len(),
+        }
+    }
+
+    /// Records the keypad, or sets it when playing back.
+    pub fn before_frame(&mut self, cpu: &mut Cpu) {
+        match self {
+            MovieState::Recording(movie) => {
+                let keys = (0..16)
+                    .filter(|key| cpu.keymap[*key])
+                    .fold(0, |keys, key| keys | 1 << key);
+                movie.frames.push(Frame {
+                    keys,
+                    checksum: None,
+                });
+            }
+            MovieState::Playing { movie, frame, .. } => {
+                if let Some(recorded) = movie.frames.get(*frame) {
+                    for key in 0..16 {
+                        cpu.set_key(key, recorded.keys & 1 << key != 0);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Takes or checks a checksum. Recordings always have one on their last frame, so it doesn't
+    /// matter where they were stopped.
+    pub fn after_frame(&mut self, cpu: &Cpu) {
+        match self {
+            MovieState::Recording(movie) => {
+                let len = movie.frames.len();
+                if len >= 2 && (len - 1) % CHECKSUM_INTERVAL != 0 {
+                    movie.frames[len - 2].checksum = None;
+                }
+                if let Some(last) = movie.frames.last_mut() {
+                    last.checksum = Some(cpu.checksum());
+                }
+            }
+            MovieState::Playing {
+                movie,
+                frame,
+                desync,
+            } => {
+                let recorded = match movie.frames.get(*frame) {
+                    Some(recorded) => recorded,
+                    None => return,
+                };
+                if desync.is_none() && recorded.checksum.is_some_and(|sum| sum != cpu.checksum()) {
+                    *desync = Some(*frame);
+                }
+                *frame += 1;
+            }
+        }
+    }
+}
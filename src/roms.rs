@@ -19,3 +19,42 @@ impl_get_bytes!(
     "MAZE", "MERLIN", "MISSILE", "PONG", "PONG2", "PUZZLE", "SYZYGY", "TANK", "TETRIS", "TICTAC",
     "UFO", "VBRIX", "VERS", "WIPEOFF"
 );
+
+/// The speed a ROM plays best at, in instructions per second.
+pub fn default_ips(file: &str) -> u32 {
+    match file {
+        // Both were written for CHIP-48 on the HP 48, which ran programs several times faster than
+        // the VIP. Hans Christian Egeberg, 1991: the maze and ghosts crawl at the default speed
+        "BLINKY" => 1000,
+        // Roy Trevino, 1990: a lighter main loop than BLINKY, so it needs less of a boost
+        "SYZYGY" => 700,
+        _ => crate::scheduler::DEFAULT_IPS,
+    }
+}
+
+/// The keys a ROM reacts to, or `None` if that isn't known or it takes any key.
+pub fn used_keys(file: &str) -> Option<&'static [usize]> {
+    Some(match file {
+        "BLINKY" => &[0x1, 0x3, 0x6, 0x7, 0x8, 0xF],
+        "BLITZ" => &[0x5],
+        "BRIX" => &[0x4, 0x6],
+        "CONNECT4" => &[0x4, 0x5, 0x6],
+        "HIDDEN" => &[0x2, 0x4, 0x5, 0x6, 0x8],
+        "INVADERS" => &[0x4, 0x5, 0x6],
+        "KALEID" => &[0x0, 0x2, 0x4, 0x6, 0x8],
+        "MAZE" => &[],
+        "MERLIN" => &[0x4, 0x5, 0x7, 0x8],
+        "MISSILE" => &[0x8],
+        "PONG" | "PONG2" => &[0x1, 0x4, 0xC, 0xD],
+        "PUZZLE" => &[0x2, 0x4, 0x6, 0x8],
+        "SYZYGY" => &[0x3, 0x6, 0x7, 0x8, 0xB, 0xE, 0xF],
+        "TANK" => &[0x2, 0x4, 0x5, 0x6, 0x8],
+        "TETRIS" => &[0x4, 0x5, 0x6, 0x7],
+        "TICTAC" => &[0x1, 0x2, 0x3, 0x4, 0x5, 0x6, 0x7, 0x8, 0x9],
+        "UFO" => &[0x4, 0x5, 0x6],
+        "VBRIX" => &[0x1, 0x4, 0x7],
+        "VERS" => &[0x1, 0x2, 0x7, 0xA, 0xB, 0xC, 0xD, 0xF],
+        "WIPEOFF" => &[0x4, 0x6],
+        _ => return None,
+    })
+}
@@ -0,0 +1,132 @@
+use crate::audio::Beeper;
+use crate::cpu::{Cpu, CpuError, VIP_CYCLES_PER_FRAME};
+use crate::movie::MovieState;
+
+pub const TIMER_HZ: u32 = 60;
+pub const DEFAULT_IPS: u32 = 480;
+
+// Don't try to catch up on more than this, e.g. after the window was in the background
+const MAX_ELAPSED: f64 = 0.25;
+
+/// Runs the CPU at a fixed number of instructions per second, and the timers at 60Hz,
+/// regardless of how often the frontend calls in. With `Cpu::vip_timing` set the instruction
+/// rate is ignored and each frame instead gets a budget of VIP machine cycles.
+pub struct Scheduler {
+    pub ips: u32,
+    /// Renders a frame of audio every tick when set, see `take_samples`
+    pub audio: Option<Beeper>,
+    samples: Vec<f32>,
+    /// Keeps a copy of the framebuffer every tick when set, see `take_frames`
+    pub record_frames: bool,
+    frames: Vec<[bool; 32 * 64]>,
+    // Framebuffers at the end of the last two ticks, oldest first
+    boundaries: [[bool; 32 * 64]; 2],
+    /// Records the keypad every tick, or plays it back, when set
+    pub movie: Option<MovieState>,
+    elapsed: f64,
+    // Instructions owed, in 1/TIMER_HZ units so fractional rates don't drift
+    budget: u32,
+    // VIP machine cycles left this frame, negative if the last instruction overran it
+    cycle_budget: i64,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new(DEFAULT_IPS)
+    }
+}
+
+impl Scheduler {
+    pub fn new(ips: u32) -> Self {
+        Self {
+            ips,
+            audio: None,
+            samples: Vec::new(),
+            record_frames: false,
+            frames: Vec::new(),
+            boundaries: [[false; 32 * 64]; 2],
+            movie: None,
+            elapsed: 0.0,
+            budget: 0,
+            cycle_budget: 0,
+        }
+    }
+
+    /// Advances emulation by `dt` seconds of wall time played back at `speed`, e.g. 4.0 for
+    /// turbo or 0.5 for slow motion, returning how many timer ticks were run.
+    pub fn update(&mut self, cpu: &mut Cpu, dt: f64, speed: f64) -> Result<usize, CpuError> {
+        // The cap is on wall time, so it doesn't limit how fast turbo can go
+        self.elapsed += dt.min(MAX_ELAPSED) * speed;
+
+        let mut ticks = 0;
+        let period = 1.0 / f64::from(TIMER_HZ);
+        while self.elapsed >= period {
+            self.elapsed -= period;
+            self.tick(cpu)?;
+            ticks += 1;
+        }
+
+        Ok(ticks)
+    }
+
+    /// Runs one frame worth of instructions, then signals the frame boundary and decrements the
+    /// timers. A CPU waiting for the display sits out the rest of the frame.
+    pub fn tick(&mut self, cpu: &mut Cpu) -> Result<(), CpuError> {
+        if let Some(movie) = &mut self.movie {
+            movie.before_frame(cpu);
+        }
+        if cpu.vip_timing {
+            self.cycle_budget += i64::from(VIP_CYCLES_PER_FRAME);
+            while self.cycle_budget > 0 {
+                let start = cpu.cycles;
+                cpu.step()?;
+                self.cycle_budget -= (cpu.cycles - start) as i64;
+                if cpu.waiting_for_vblank() {
+                    self.cycle_budget = self.cycle_budget.min(0);
+                    break;
+                }
+            }
+        } else {
+            self.budget += self.ips;
+            while self.budget >= TIMER_HZ {
+                cpu.step()?;
+                self.budget -= TIMER_HZ;
+                if cpu.waiting_for_vblank() {
+                    self.budget %= TIMER_HZ;
+                    break;
+                }
+            }
+        }
+
+        if let Some(beeper) = &mut self.audio {
+            beeper.render_frame(cpu, &mut self.samples);
+        }
+        if self.record_frames {
+            self.frames.push(*cpu.get_framebuffer());
+        }
+        self.boundaries = [self.boundaries[1], *cpu.get_framebuffer()];
+
+        cpu.vblank();
+        cpu.dec_regs();
+        if let Some(movie) = &mut self.movie {
+            movie.after_frame(cpu);
+        }
+        Ok(())
+    }
+
+    /// Audio rendered since the last call, to be handed to the output device.
+    pub fn take_samples(&mut self) -> Vec<f32> {
+        std::mem::take(&mut self.samples)
+    }
+
+    /// The framebuffer at the end of the tick before the last one, however many ticks ran since
+    /// the frontend last looked. Stepping single instructions doesn't move it on.
+    pub fn previous_frame(&self) -> &[bool; 32 * 64] {
+        &self.boundaries[0]
+    }
+
+    /// Framebuffers at the end of each tick since the last call.
+    pub fn take_frames(&mut self) -> Vec<[bool; 32 * 64]> {
+        std::mem::take(&mut self.frames)
+    }
+}
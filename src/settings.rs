@@ -0,0 +1,212 @@
+//! Keeps the menu settings between runs, as TOML: in the platform's config directory natively,
+//! in localStorage in the browser through the small plugin in `js/chip8-storage.js`.
+
+use crate::ui::MenuState;
+
+/// The settings as saved. Values come before tables, which TOML requires.
+pub fn to_text(menu_state: &MenuState) -> Result<String, String> {
+    toml::Value::try_from(menu_state)
+        .map(|value| value.to_string())
+        .map_err(|err| format!("Couldn't save settings: {}", err))
+}
+
+/// Settings missing from `text` keep their defaults, ones out of range are brought into it.
+pub fn from_text(text: &str) -> Result<MenuState, String> {
+    let mut menu_state: MenuState =
+        toml::from_str(text).map_err(|err| format!("Couldn't load settings: {}", err))?;
+    menu_state.validate();
+    Ok(menu_state)
+}
+
+/// The saved settings, or the defaults if there aren't any yet.
+pub fn load() -> Result<MenuState, String> {
+    match storage::read()? {
+        Some(text) => from_text(&text),
+        None => Ok(MenuState::default()),
+    }
+}
+
+pub fn save(text: &str) -> Result<(), String> {
+    storage::write(text)
+}
+
+/// Shader parameters are keyed by the `&'static str` names from `shaders::default_params`, so only
+/// those get loaded.
+pub mod shader_params {
+    use crate::shaders::default_params;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::HashMap;
+
+    pub fn serialize<S: Serializer>(
+        params: &HashMap<&'static str, f32>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        params.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<&'static str, f32>, D::Error> {
+        let saved = HashMap::<String, f32>::deserialize(deserializer)?;
+        let mut params = default_params();
+        for (name, value) in params.iter_mut() {
+            if let Some(saved) = saved.get(*name) {
+                *value = *saved;
+            }
+        }
+        Ok(params)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod storage {
+    use std::path::PathBuf;
+    use std::{fs, io};
+
+    fn path() -> Result<PathBuf, String> {
+        dirs::config_dir()
+            .map(|dir| dir.join("wasm-chip8").join("settings.toml"))
+            .ok_or_else(|| "No config directory to keep settings in".to_string())
+    }
+
+    pub fn read() -> Result<Option<String>, String> {
+        let path = path()?;
+        match fs::read_to_string(&path) {
+            Ok(text) => Ok(Some(text)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(format!("Couldn't read {}: {}", path.display(), err)),
+        }
+    }
+
+    pub fn write(text: &str) -> Result<(), String> {
+        let path = path()?;
+        let error = |err| format!("Couldn't save {}: {}", path.display(), err);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(error)?;
+        }
+        fs::write(&path, text).map_err(error)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod storage {
+    const KEY: &str = "chip8-settings";
+
+    extern "C" {
+        fn chip8_storage_len(key: *const u8, key_len: u32) -> i32;
+        fn chip8_storage_get(key: *const u8, key_len: u32, buf: *mut u8, buf_len: u32);
+        fn chip8_storage_set(key: *const u8, key_len: u32, value: *const u8, value_len: u32)
+            -> u32;
+    }
+
+    pub fn read() -> Result<Option<String>, String> {
+        // Negative if nothing was saved
+        let len = unsafe { chip8_storage_len(KEY.as_ptr(), KEY.len() as u32) };
+        if len < 0 {
+            return Ok(None);
+        }
+        let mut buf = vec![0; len as usize];
+        unsafe { chip8_storage_get(KEY.as_ptr(), KEY.len() as u32, buf.as_mut_ptr(), len as u32) };
+        String::from_utf8(buf)
+            .map(Some)
+            .map_err(|_| "Saved settings aren't valid text".to_string())
+    }
+
+    pub fn write(text: &str) -> Result<(), String> {
+        let saved = unsafe {
+            chip8_storage_set(
+                KEY.as_ptr(),
+                KEY.len() as u32,
+                text.as_ptr(),
+                text.len() as u32,
+            )
+        };
+        match saved {
+            0 => Err("Couldn't save settings, storage may be disabled or full".to_string()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use macroquad::prelude::KeyCode;
+    use wasm_chip8::keymap::{Button, Keymap, PadMap};
+
+    fn saved(menu_state: &MenuState) -> toml::Value {
+        toml::Value::try_from(menu_state).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let text = to_text(&from_text("ips = 700\npalette = \"Amber\"\n").unwrap()).unwrap();
+        let loaded = from_text(&text).unwrap();
+        assert_eq!(to_text(&loaded).unwrap(), text);
+        assert_eq!(saved(&loaded)["ips"].as_integer(), Some(700));
+        assert_eq!(saved(&loaded)["palette"].as_str(), Some("Amber"));
+    }
+
+    #[test]
+    fn missing_settings_keep_defaults() {
+        let mut expected = saved(&MenuState::default());
+        expected["border"] = toml::Value::Integer(5);
+        assert_eq!(saved(&from_text("border = 5").unwrap()), expected);
+    }
+
+    #[test]
+    fn out_of_range_settings_are_fixed() {
+        let text = "selected = \"NOSUCHROM\"\nips = 5\nborder = 100\ndecay = 2.0\n\
+                    screenshot_scale = 0\nrecording_scale = 50\npalette = \"Plaid\"\n\
+                    shader_preset = \"Sepia\"\n";
+        let loaded = saved(&from_text(text).unwrap());
+        let defaults = saved(&MenuState::default());
+        for name in ["selected", "palette", "shader_preset"].iter() {
+            assert_eq!(loaded[name], defaults[name], "{}", name);
+        }
+        assert_eq!(loaded["ips"].as_integer(), Some(60));
+        assert_eq!(loaded["border"].as_integer(), Some(8));
+        assert_eq!(loaded["decay"].as_float(), Some(0.95f32 as f64));
+        assert_eq!(loaded["screenshot_scale"].as_integer(), Some(2));
+        assert_eq!(loaded["recording_scale"].as_integer(), Some(8));
+
+        let custom = saved(&from_text("palette = \"Custom\"").unwrap());
+        assert_eq!(custom["palette"].as_str(), Some("Custom"));
+    }
+
+    #[test]
+    fn bindings_saved_by_name() {
+        let text = to_text(&MenuState::default()).unwrap();
+        assert!(text.contains(r#"["Z", "Y"]"#), "{}", text);
+        assert!(text.contains(r#"["DPadUp", "StickUp"]"#), "{}", text);
+
+        let loaded = from_text(&text).unwrap();
+        assert_eq!(loaded.controls("TETRIS").keys, Keymap::default());
+        assert_eq!(loaded.controls("TETRIS").pad, PadMap::default());
+    }
+
+    #[test]
+    fn missing_bindings_keep_defaults() {
+        let bindings = |first: &str| {
+            let mut names = vec![first.to_string()];
+            names.resize(16, "[]".to_string());
+            names.join(", ")
+        };
+        let text = format!(
+            "[controls]\npad = [{}]\n\n[rom_controls.BRIX]\nkeys = [{}]\n",
+            bindings(r#"["South", "NoSuchButton"]"#),
+            bindings(r#"["Space", "NoSuchKey"]"#)
+        );
+        let loaded = from_text(&text).unwrap();
+
+        let shared = loaded.controls("TETRIS");
+        assert_eq!(shared.keys, Keymap::default());
+        assert_eq!(shared.pad.keys[0], vec![Button::South]);
+        assert!(shared.pad.keys[1..].iter().all(Vec::is_empty));
+
+        let brix = loaded.controls("BRIX");
+        assert_eq!(brix.keys.keys[0], vec![KeyCode::Space]);
+        assert!(brix.keys.keys[1..].iter().all(Vec::is_empty));
+        assert_eq!(brix.pad, PadMap::default());
+    }
+}
@@ -0,0 +1,265 @@
+use macroquad::prelude::*;
+use std::collections::HashMap;
+
+// Intermediate passes render at this multiple of the emulator's render target
+const PASS_SCALE: f32 = 8.0;
+
+/// A shader uniform that can be tweaked from the menu.
+pub struct Param {
+    pub name: &'static str,
+    pub label: &'static str,
+    pub default: f32,
+    pub min: f32,
+    pub max: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Effect {
+    Crt,
+    Lcd,
+    Smooth,
+    Bloom,
+}
+
+impl Effect {
+    pub const ALL: [Effect; 4] = [Effect::Crt, Effect::Lcd, Effect::Smooth, Effect::Bloom];
+
+    fn fragment(self) -> &'static str {
+        match self {
+            Effect::Crt => include_str!("CRT_shader.frag"),
+            Effect::Lcd => include_str!("LCD_shader.frag"),
+            Effect::Smooth => include_str!("smooth_shader.frag"),
+            Effect::Bloom => include_str!("bloom_shader.frag"),
+        }
+    }
+
+    pub fn params(self) -> &'static [Param] {
+        match self {
+            Effect::Crt => &[
+                Param {
+                    name: "Curvature",
+                    label: "Screen curvature",
+                    default: 1.0,
+                    min: 0.0,
+                    max: 2.0,
+                },
+                Param {
+                    name: "Vignette",
+                    label: "Vignette",
+                    default: 1.0,
+                    min: 0.0,
+                    max: 1.0,
+                },
+                Param {
+                    name: "Scanlines",
+                    label: "Scanline strength",
+                    default: 0.05,
+                    min: 0.0,
+                    max: 0.5,
+                },
+                Param {
+                    name: "Grille",
+                    label: "Aperture grille strength",
+                    default: 0.15,
+                    min: 0.0,
+                    max: 0.5,
+                },
+                Param {
+                    name: "Brightness",
+                    label: "Brightness",
+                    default: 1.2,
+                    min: 0.5,
+                    max: 2.0,
+                },
+            ],
+            Effect::Lcd => &[
+                Param {
+                    name: "GridWidth",
+                    label: "LCD grid width",
+                    default: 0.1,
+                    min: 0.0,
+                    max: 0.5,
+                },
+                Param {
+                    name: "GridStrength",
+                    label: "LCD grid strength",
+                    default: 0.4,
+                    min: 0.0,
+                    max: 1.0,
+                },
+            ],
+            Effect::Smooth => &[Param {
+                name: "Strength",
+                label: "Smoothing strength",
+                default: 1.0,
+                min: 0.0,
+                max: 1.0,
+            }],
+            Effect::Bloom => &[
+                Param {
+                    name: "BloomRadius",
+                    label: "Bloom radius",
+                    default: 1.5,
+                    min: 0.5,
+                    max: 4.0,
+                },
+                Param {
+                    name: "BloomStrength",
+                    label: "Bloom strength",
+                    default: 0.6,
+                    min: 0.0,
+                    max: 2.0,
+                },
+            ],
+        }
+    }
+}
+
+/// Named chains of effects, applied in order.
+pub const PRESETS: [(&str, &[Effect]); 7] = [
+    ("Pixel perfect", &[]),
+    ("CRT", &[Effect::Crt]),
+    ("LCD", &[Effect::Lcd]),
+    ("Smooth", &[Effect::Smooth]),
+    ("Bloom", &[Effect::Bloom]),
+    ("Smooth + CRT", &[Effect::Smooth, Effect::Crt]),
+    ("Bloom + CRT", &[Effect::Bloom, Effect::Crt]),
+];
+
+/// Uniform values for every effect, starting at their defaults.
+pub fn default_params() -> HashMap<&'static str, f32> {
+    Effect::ALL
+        .iter()
+        .flat_map(|effect| effect.params())
+        .map(|param| (param.name, param.default))
+        .collect()
+}
+
+/// Draws the emulator's render target to the screen through a chain of effects.
+pub struct Pipeline {
+    materials: Vec<(Effect, Material)>,
+    targets: [RenderTarget; 2],
+    source_size: Vec2,
+}
+
+impl Pipeline {
+    pub fn new(source_size: Vec2) -> Self {
+        let materials = Effect::ALL
+            .iter()
+            .map(|effect| {
+                let mut uniforms = vec![("SourceSize".to_string(), UniformType::Float2)];
+                uniforms.extend(
+                    effect
+                        .params()
+                        .iter()
+                        .map(|param| (param.name.to_string(), UniformType::Float1)),
+                );
+                let material = load_material(
+                    include_str!("CRT_shader.vert"),
+                    effect.fragment(),
+                    MaterialParams {
+                        uniforms,
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+                (*effect, material)
+            })
+            .collect();
+
+        Self {
+            materials,
+            targets: pass_targets(source_size),
+            source_size,
+        }
+    }
+
+    /// Changes the size of the images passed to `draw`.
+    pub fn resize(&mut self, source_size: Vec2) {
+        for target in self.targets.iter() {
+            target.texture.delete();
+        }
+        self.targets = pass_targets(source_size);
+        self.source_size = source_size;
+    }
+
+    /// Draws `source` into `dest` on the screen. Every pass but the last renders into an
+    /// intermediate target, which the next pass reads from.
+    pub fn draw(
+        &self,
+        source: Texture2D,
+        effects: &[Effect],
+        params: &HashMap<&'static str, f32>,
+        dest: Rect,
+    ) {
+        let mut input = source;
+        for (idx, effect) in effects.iter().enumerate() {
+            let material = self.material(*effect);
+            material.set_uniform("SourceSize", [self.source_size.x, self.source_size.y]);
+            for param in effect.params() {
+                material.set_uniform(param.name, params[param.name]);
+            }
+
+            let last = idx + 1 == effects.len();
+            let target = self.targets[idx % 2];
+            let rect = if last {
+                dest
+            } else {
+                let size = self.source_size * PASS_SCALE;
+                let rect = Rect::new(0.0, 0.0, size.x, size.y);
+                set_camera(&Camera2D {
+                    render_target: Some(target),
+                    ..Camera2D::from_display_rect(rect)
+                });
+                rect
+            };
+
+            gl_use_material(material);
+            draw_render_texture(input, rect);
+            gl_use_default_material();
+
+            if !last {
+                set_default_camera();
+                input = target.texture;
+            }
+        }
+
+        if effects.is_empty() {
+            draw_render_texture(source, dest);
+        }
+    }
+
+    fn material(&self, effect: Effect) -> Material {
+        self.materials
+            .iter()
+            .find(|(e, _)| *e == effect)
+            .map(|(_, material)| *material)
+            .unwrap()
+    }
+}
+
+fn pass_targets(source_size: Vec2) -> [RenderTarget; 2] {
+    let size = source_size * PASS_SCALE;
+    let targets = [
+        render_target(size.x as u32, size.y as u32),
+        render_target(size.x as u32, size.y as u32),
+    ];
+    for target in targets.iter() {
+        target.texture.set_filter(FilterMode::Nearest);
+    }
+    targets
+}
+
+fn draw_render_texture(texture: Texture2D, rect: Rect) {
+    draw_texture_ex(
+        texture,
+        rect.x,
+        rect.y,
+        WHITE,
+        DrawTextureParams {
+            dest_size: Some(vec2(rect.w, rect.h)),
+            flip_y: true,
+            ..Default::default()
+        },
+    );
+}
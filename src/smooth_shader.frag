@@ -0,0 +1,36 @@
+precision mediump float;
+varying vec4 color;
+varying vec2 uv;
+
+uniform sampler2D Texture;
+uniform vec2 SourceSize;
+uniform float Strength;
+
+// Scale2x: rounds off diagonal staircases by looking at the four neighbours of each pixel
+bool same(vec3 a, vec3 b) {
+    return distance(a, b) < 0.1;
+}
+
+void main() {
+    vec2 texel = 1.0 / SourceSize;
+    vec2 center = (floor(uv * SourceSize) + 0.5) * texel;
+    vec2 quadrant = step(0.5, fract(uv * SourceSize));
+
+    vec3 e = texture2D(Texture, center).rgb;
+    vec3 a = texture2D(Texture, center - vec2(0.0, texel.y)).rgb;
+    vec3 b = texture2D(Texture, center + vec2(texel.x, 0.0)).rgb;
+    vec3 c = texture2D(Texture, center - vec2(texel.x, 0.0)).rgb;
+    vec3 d = texture2D(Texture, center + vec2(0.0, texel.y)).rgb;
+
+    // The neighbours adjacent to this quarter of the pixel, and the two opposite ones
+    vec3 vertical = quadrant.y < 0.5 ? a : d;
+    vec3 horizontal = quadrant.x < 0.5 ? c : b;
+    vec3 vertical_opposite = quadrant.y < 0.5 ? d : a;
+    vec3 horizontal_opposite = quadrant.x < 0.5 ? b : c;
+
+    vec3 res = e;
+    if (same(vertical, horizontal) && !same(vertical, vertical_opposite) && !same(horizontal, horizontal_opposite)) {
+        res = vertical;
+    }
+    gl_FragColor = vec4(mix(e, res, Strength) * color.rgb, 1.0);
+}
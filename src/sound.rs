@@ -0,0 +1,130 @@
+//! Streams the samples rendered by the emulator to the speakers. Natively this goes through
+//! rodio, in the browser through the small WebAudio plugin in `js/chip8-audio.js`.
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use native::AudioOut;
+#[cfg(target_arch = "wasm32")]
+pub use web::AudioOut;
+
+// Drop samples rather than letting latency build up, e.g. while in turbo
+const MAX_QUEUED_MS: u32 = 250;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use rodio::{OutputStream, Source};
+    use wasm_chip8::audio::DEFAULT_SAMPLE_RATE;
+
+    // Per sample decay of the held sample once the queue runs dry, fading out in a few milliseconds
+    const UNDERRUN_FADE: f32 = 0.995;
+
+    struct Stream {
+        queue: Arc<Mutex<VecDeque<f32>>>,
+        last: f32,
+    }
+
+    impl Iterator for Stream {
+        type Item = f32;
+
+        fn next(&mut self) -> Option<f32> {
+            // When the emulator falls behind, fade out from the last sample instead of jumping
+            // straight to silence, which clicks. The stream itself never ends
+            self.last = match self.queue.lock().unwrap().pop_front() {
+                Some(sample) => sample,
+                None => self.last * UNDERRUN_FADE,
+            };
+            Some(self.last)
+        }
+    }
+
+    impl Source for Stream {
+        fn current_frame_len(&self) -> Option<usize> {
+            None
+        }
+
+        fn channels(&self) -> u16 {
+            1
+        }
+
+        fn sample_rate(&self) -> u32 {
+            DEFAULT_SAMPLE_RATE
+        }
+
+        fn total_duration(&self) -> Option<Duration> {
+            None
+        }
+    }
+
+    pub struct AudioOut {
+        _stream: OutputStream,
+        queue: Arc<Mutex<VecDeque<f32>>>,
+    }
+
+    impl AudioOut {
+        /// Returns `None` if there is no audio device to play on.
+        pub fn new() -> Option<Self> {
+            let (stream, handle) = OutputStream::try_default().ok()?;
+            let queue = Arc::new(Mutex::new(VecDeque::new()));
+            handle
+                .play_raw(Stream {
+                    queue: queue.clone(),
+                    last: 0.0,
+                })
+                .ok()?;
+
+            Some(Self {
+                _stream: stream,
+                queue,
+            })
+        }
+
+        pub fn sample_rate(&self) -> u32 {
+            DEFAULT_SAMPLE_RATE
+        }
+
+        pub fn push(&mut self, samples: &[f32]) {
+            let max = (DEFAULT_SAMPLE_RATE * super::MAX_QUEUED_MS / 1000) as usize;
+            let mut queue = self.queue.lock().unwrap();
+            queue.extend(samples);
+            let excess = queue.len().saturating_sub(max);
+            queue.drain(..excess);
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod web {
+    extern "C" {
+        fn chip8_audio_init() -> u32;
+        fn chip8_audio_push(samples: *const f32, len: u32, max_queued: f32);
+    }
+
+    pub struct AudioOut {
+        sample_rate: u32,
+    }
+
+    impl AudioOut {
+        /// Returns `None` if the browser doesn't support WebAudio.
+        pub fn new() -> Option<Self> {
+            match unsafe { chip8_audio_init() } {
+                0 => None,
+                sample_rate => Some(Self { sample_rate }),
+            }
+        }
+
+        pub fn sample_rate(&self) -> u32 {
+            self.sample_rate
+        }
+
+        pub fn push(&mut self, samples: &[f32]) {
+            if samples.is_empty() {
+                return;
+            }
+            let max_queued = super::MAX_QUEUED_MS as f32 / 1000.0;
+            unsafe { chip8_audio_push(samples.as_ptr(), samples.len() as u32, max_queued) }
+        }
+    }
+}
@@ -1,21 +1,178 @@
-use crate::roms::ROMS;
+use crate::gamepad::Gamepads;
+use crate::shaders::{default_params, Effect, PRESETS};
 use crate::State;
+use macroquad::prelude::{get_last_key_pressed, is_key_pressed, KeyCode};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::ops::RangeInclusive;
+use wasm_chip8::roms::{default_ips, ROMS};
 
-use crate::cpu::Cpu;
-use crate::disassembler::{generate_disassembly, highlight};
+use wasm_chip8::audio::Waveform;
+use wasm_chip8::cpu::{Cpu, CpuError, KeyWait};
+use wasm_chip8::disassembler::{generate_disassembly, highlight};
+use wasm_chip8::display::{preset, Palette, Persistence, ScaleMode, PALETTES};
+use wasm_chip8::keymap::{Bindings, Keymap, PadMap, KEYPAD, LAYOUTS};
 
+const CUSTOM_PALETTE: &str = "Custom";
+// Custom palettes only set the colours the display draws with. XO-CHIP's second plane isn't
+// emulated, so its colours just repeat the foreground
+const PALETTE_LABELS: [&str; 2] = ["Background", "Foreground"];
+
+// Slider ranges, which loaded settings are held to as well
+const IPS_RANGE: RangeInclusive<u32> = 60..=2000;
+const TURBO_RANGE: RangeInclusive<u32> = 2..=16;
+const BORDER_RANGE: RangeInclusive<u32> = 0..=8;
+const DECAY_RANGE: RangeInclusive<f32> = 0.0..=0.95;
+const FREQUENCY_RANGE: RangeInclusive<f32> = 110.0..=1760.0;
+const VOLUME_RANGE: RangeInclusive<f32> = 0.0..=1.0;
+const SCREENSHOT_SCALE_RANGE: RangeInclusive<u16> = 2..=16;
+const RECORDING_SCALE_RANGE: RangeInclusive<u16> = 1..=8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ScreenshotMode {
+    /// The framebuffer at its native resolution
+    Raw,
+    /// The framebuffer scaled up by `MenuState::screenshot_scale`
+    Scaled,
+    /// What's on screen, including the shader effects
+    Screen,
+}
+
+impl ScreenshotMode {
+    pub const ALL: [ScreenshotMode; 3] = [
+        ScreenshotMode::Raw,
+        ScreenshotMode::Scaled,
+        ScreenshotMode::Screen,
+    ];
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum RecordingFormat {
+    Gif,
+    /// Only natively, the browser would ask about every single file
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    PngSequence,
+}
+
+impl RecordingFormat {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub const ALL: &'static [RecordingFormat] =
+        &[RecordingFormat::Gif, RecordingFormat::PngSequence];
+    #[cfg(target_arch = "wasm32")]
+    pub const ALL: &'static [RecordingFormat] = &[RecordingFormat::Gif];
+}
+
+/// What to do with an input movie when a game starts.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum MovieMode {
+    #[default]
+    Off,
+    Record,
+    /// Only natively, as the file has to be read back in
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    Play,
+}
+
+impl MovieMode {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub const ALL: &'static [MovieMode] = &[MovieMode::Off, MovieMode::Record, MovieMode::Play];
+    #[cfg(target_arch = "wasm32")]
+    pub const ALL: &'static [MovieMode] = &[MovieMode::Off, MovieMode::Record];
+}
+
+/// Where the on-screen keypad for touch screens goes.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TouchKeypad {
+    /// Below the display once the screen has been touched
+    Auto,
+    Off,
+    Below,
+    /// Translucent, over the display
+    Overlay,
+}
+
+impl TouchKeypad {
+    pub const ALL: [TouchKeypad; 4] = [
+        TouchKeypad::Auto,
+        TouchKeypad::Off,
+        TouchKeypad::Below,
+        TouchKeypad::Overlay,
+    ];
+}
+
+/// Keyboard and gamepad bindings, either shared or for a single ROM.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Controls {
+    pub keys: Keymap,
+    pub pad: PadMap,
+}
+
+/// A CHIP-8 key waiting for a host key or gamepad button to be pressed.
+#[derive(Clone, Copy, PartialEq)]
+enum Binding {
+    Key(usize),
+    Button(usize),
+}
+
+/// Everything set in the menu. Saved between runs by `settings`, apart from what's skipped.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct MenuState {
     selected: String,
+    #[serde(skip)]
     show_about: bool,
     pub show_debugger: bool,
     pub load_store_compat: bool,
     pub shift_compat: bool,
-    pub alpha: u8,
-    pub crt_shader: bool,
+    pub ips: u32,
+    // Speed last set for each ROM
+    rom_ips: HashMap<String, u32>,
+    pub vip_timing: bool,
+    pub display_wait: bool,
+    pub turbo: u32,
+    pub turbo_uncapped: bool,
+    pub waveform: Waveform,
+    pub frequency: f32,
+    pub volume: f32,
+    pub muted: bool,
+    palette: String,
+    custom_palette: [String; 2],
+    // Palette last used with each ROM
+    rom_palettes: HashMap<String, String>,
+    pub persistence: Persistence,
+    pub decay: f32,
+    pub scale_mode: ScaleMode,
+    /// Margin around the framebuffer, in CHIP-8 pixels
+    pub border: u32,
+    pub fullscreen: bool,
+    shader_preset: String,
+    #[serde(with = "crate::settings::shader_params")]
+    pub shader_params: HashMap<&'static str, f32>,
+    pub screenshot_mode: ScreenshotMode,
+    pub screenshot_scale: u16,
+    pub recording_format: RecordingFormat,
+    pub recording_scale: u16,
+    // Starting every game as a recording or replay after a restart would be a surprise
+    #[serde(skip)]
+    pub movie_mode: MovieMode,
+    /// The movie to play back, the game comes from the movie rather than the selection
+    pub movie_path: String,
+    controls: Controls,
+    pub touch_keypad: TouchKeypad,
+    pub hide_unused_keys: bool,
+    // Bindings for ROMs that don't use the shared ones
+    rom_controls: HashMap<String, Controls>,
+    #[serde(skip)]
+    binding: Option<Binding>,
 }
 
 pub struct DebuggerState {
     pub running: bool,
+    pub error: Option<CpuError>,
+    /// Set when a screenshot was requested from the debugger
+    pub screenshot: bool,
     delay_counter: u32,
 }
 
@@ -27,23 +184,167 @@ impl Default for MenuState {
             show_debugger: false,
             load_store_compat: false,
             shift_compat: false,
-            alpha: 64,
-            crt_shader: true,
+            ips: default_ips("TETRIS"),
+            rom_ips: HashMap::new(),
+            vip_timing: false,
+            display_wait: false,
+            turbo: 4,
+            turbo_uncapped: false,
+            waveform: Waveform::Square,
+            frequency: 440.0,
+            volume: 0.25,
+            muted: false,
+            palette: PALETTES[0].0.to_string(),
+            custom_palette: [PALETTES[0].1[0].to_string(), PALETTES[0].1[1].to_string()],
+            rom_palettes: HashMap::new(),
+            persistence: Persistence::Decay,
+            decay: 0.5,
+            scale_mode: ScaleMode::Fit,
+            border: 2,
+            fullscreen: false,
+            shader_preset: "CRT".to_string(),
+            shader_params: default_params(),
+            screenshot_mode: ScreenshotMode::Scaled,
+            screenshot_scale: 8,
+            recording_format: RecordingFormat::Gif,
+            recording_scale: 4,
+            movie_mode: MovieMode::Off,
+            movie_path: String::new(),
+            controls: Controls::default(),
+            touch_keypad: TouchKeypad::Auto,
+            hide_unused_keys: true,
+            rom_controls: HashMap::new(),
+            binding: None,
         }
     }
 }
 
+impl MenuState {
+    pub fn shader_effects(&self) -> &'static [Effect] {
+        PRESETS
+            .iter()
+            .find(|(name, _)| *name == self.shader_preset)
+            .map_or(&[], |(_, effects)| effects)
+    }
+
+    /// The bindings used for `rom`.
+    pub fn controls(&self, rom: &str) -> &Controls {
+        self.rom_controls.get(rom).unwrap_or(&self.controls)
+    }
+
+    fn selected_controls_mut(&mut self) -> &mut Controls {
+        match self.rom_controls.get_mut(&self.selected) {
+            Some(controls) => controls,
+            None => &mut self.controls,
+        }
+    }
+
+    /// Replaces settings the menu couldn't have made, e.g. from an edited or outdated settings
+    /// file, so nothing downstream has to cope with them.
+    pub fn validate(&mut self) {
+        let defaults = MenuState::default();
+        if !ROMS.contains(&self.selected.as_str()) {
+            self.selected = defaults.selected;
+        }
+        if !is_palette(&self.palette) {
+            self.palette = defaults.palette;
+        }
+        self.rom_palettes.retain(|_, palette| is_palette(palette));
+        if !PRESETS.iter().any(|(name, _)| *name == self.shader_preset) {
+            self.shader_preset = defaults.shader_preset;
+        }
+
+        self.ips = clamp(self.ips, IPS_RANGE);
+        for ips in self.rom_ips.values_mut() {
+            *ips = clamp(*ips, IPS_RANGE);
+        }
+        self.turbo = clamp(self.turbo, TURBO_RANGE);
+        self.border = clamp(self.border, BORDER_RANGE);
+        self.decay = clamp(self.decay, DECAY_RANGE);
+        self.frequency = clamp(self.frequency, FREQUENCY_RANGE);
+        self.volume = clamp(self.volume, VOLUME_RANGE);
+        self.screenshot_scale = clamp(self.screenshot_scale, SCREENSHOT_SCALE_RANGE);
+        self.recording_scale = clamp(self.recording_scale, RECORDING_SCALE_RANGE);
+    }
+
+    /// The selected palette, falling back to the first preset if custom colours don't parse.
+    pub fn palette(&self) -> Palette {
+        let [background, foreground] = &self.custom_palette;
+        preset(&self.palette)
+            .or_else(|| Palette::from_hex(&[background, foreground, foreground, foreground]))
+            .unwrap_or_else(|| Palette::from_hex(&PALETTES[0].1).unwrap())
+    }
+}
+
+fn is_palette(name: &str) -> bool {
+    name == CUSTOM_PALETTE || preset(name).is_some()
+}
+
+/// `value` moved into `range`, to its start if it isn't a number.
+fn clamp<T: PartialOrd + Copy>(value: T, range: RangeInclusive<T>) -> T {
+    if value > *range.end() {
+        *range.end()
+    } else if range.contains(&value) {
+        value
+    } else {
+        *range.start()
+    }
+}
+
 impl Default for DebuggerState {
     fn default() -> Self {
         Self {
             running: true,
+            error: None,
+            screenshot: false,
             delay_counter: 0,
         }
     }
 }
 
-pub fn show_menu(state: &mut State, menu_state: &mut MenuState) {
+impl DebuggerState {
+    /// Stops the CPU and keeps the error around to be shown in the debugger.
+    pub fn halt(&mut self, err: CpuError) {
+        self.running = false;
+        self.error = Some(err);
+    }
+}
+
+/// Returns whether the settings may have changed, i.e. the menu took input this frame.
+pub fn show_menu(state: &mut State, menu_state: &mut MenuState, gamepads: &Gamepads) -> bool {
+    let bound = menu_state.binding;
+    // Escape cancels, it's needed to leave the game
+    match menu_state.binding {
+        Some(Binding::Key(key)) => {
+            if let Some(host) = get_last_key_pressed() {
+                menu_state.binding = None;
+                if host != KeyCode::Escape {
+                    menu_state.selected_controls_mut().keys.bind(key, host);
+                }
+            }
+        }
+        Some(Binding::Button(key)) => {
+            if let Some(button) = gamepads.last_pressed() {
+                menu_state.binding = None;
+                menu_state.selected_controls_mut().pad.bind(key, button);
+            } else if is_key_pressed(KeyCode::Escape) {
+                menu_state.binding = None;
+            }
+        }
+        None => {}
+    }
+
+    let mut reset = false;
+    let mut changed = menu_state.binding != bound;
     egui_macroquad::ui(|egui_ctx| {
+        // Every setting is changed by a click, a released drag or typing
+        let input = egui_ctx.input();
+        changed |= input.pointer.any_released()
+            || input
+                .events
+                .iter()
+                .any(|event| matches!(event, egui::Event::Key { .. } | egui::Event::Text(_)));
+
         egui::Window::new("Menu")
             .default_width(500.0)
             .show(egui_ctx, |ui| {
@@ -60,6 +361,7 @@ pub fn show_menu(state: &mut State, menu_state: &mut MenuState) {
                     });
                 }
                 ui.separator();
+                let previous = menu_state.selected.clone();
                 egui::ComboBox::from_label("Select a game...")
                     .width(128.0)
                     .selected_text(&menu_state.selected)
@@ -68,35 +370,250 @@ pub fn show_menu(state: &mut State, menu_state: &mut MenuState) {
                             ui.selectable_value(&mut menu_state.selected, rom.to_string(), *rom);
                         }
                     });
+                if menu_state.selected != previous {
+                    menu_state.ips = match menu_state.rom_ips.get(&menu_state.selected) {
+                        Some(ips) => *ips,
+                        None => default_ips(&menu_state.selected),
+                    };
+                    if let Some(palette) = menu_state.rom_palettes.get(&menu_state.selected) {
+                        menu_state.palette = palette.clone();
+                    }
+                }
+                ui.checkbox(&mut menu_state.display_wait, "Enable display wait. Drawing waits for the next frame like on the original interpreter, which stops tearing in games like BLITZ");
+                ui.checkbox(&mut menu_state.vip_timing, "Enable COSMAC VIP timing. Instructions take as long as on the original interpreter.");
+                let previous_ips = menu_state.ips;
+                ui.vertical(|ui| {
+                    ui.set_enabled(!menu_state.vip_timing);
+                    ui.add(egui::Slider::new(&mut menu_state.ips, IPS_RANGE).text("CPU speed in instructions per second. Defaults to what the selected game plays best at."));
+                });
+                if menu_state.ips != previous_ips {
+                    menu_state.rom_ips.insert(menu_state.selected.clone(), menu_state.ips);
+                }
+                ui.add(egui::Slider::new(&mut menu_state.turbo, TURBO_RANGE).text("Turbo speed multiplier"));
+                ui.checkbox(&mut menu_state.turbo_uncapped, "Uncapped turbo. Runs as fast as the host allows instead.");
                 ui.checkbox(&mut menu_state.show_debugger, "Enable Debugger");
                 ui.checkbox(&mut menu_state.load_store_compat, "Enable load/store compatibility mode. Required for some games, like CONNECT4 and TICTAC");
                 ui.checkbox(&mut menu_state.shift_compat, "Enable shift compatibility mode. Required for some games, like TICTAC");
                 ui.separator();
-                ui.add(egui::Slider::new(&mut menu_state.alpha, 0..=255).text("Alpha value of black pixels. Lower values reduce flickering but introduce ghosting."));
-                ui.checkbox(&mut menu_state.crt_shader, "Enable CRT shader");
+                egui::ComboBox::from_label("Scaling")
+                    .width(128.0)
+                    .selected_text(format!("{:?}", menu_state.scale_mode))
+                    .show_ui(ui, |ui| {
+                        for mode in ScaleMode::ALL.iter() {
+                            ui.selectable_value(&mut menu_state.scale_mode, *mode, format!("{:?}", mode));
+                        }
+                    });
+                ui.add(egui::Slider::new(&mut menu_state.border, BORDER_RANGE).text("Border width in pixels"));
+                ui.checkbox(&mut menu_state.fullscreen, "Fullscreen. Only supported on Windows and in the browser.");
+                egui::ComboBox::from_label("Anti-flicker")
+                    .width(128.0)
+                    .selected_text(format!("{:?}", menu_state.persistence))
+                    .show_ui(ui, |ui| {
+                        for persistence in Persistence::ALL.iter() {
+                            ui.selectable_value(&mut menu_state.persistence, *persistence, format!("{:?}", persistence));
+                        }
+                    });
+                if menu_state.persistence == Persistence::Decay {
+                    ui.add(egui::Slider::new(&mut menu_state.decay, DECAY_RANGE).text("Brightness unlit pixels keep each frame. Higher values hide more flicker but leave trails."));
+                }
+                egui::ComboBox::from_label("Shader preset")
+                    .width(128.0)
+                    .selected_text(&menu_state.shader_preset)
+                    .show_ui(ui, |ui| {
+                        for (name, _) in PRESETS.iter() {
+                            ui.selectable_value(&mut menu_state.shader_preset, name.to_string(), *name);
+                        }
+                    });
+                egui::CollapsingHeader::new("Shader settings").show(ui, |ui| {
+                    for effect in menu_state.shader_effects() {
+                        for param in effect.params() {
+                            let value = menu_state.shader_params.get_mut(param.name).unwrap();
+                            ui.add(egui::Slider::new(value, param.min..=param.max).text(param.label));
+                        }
+                    }
+                    if ui.button("Reset to defaults").clicked() {
+                        menu_state.shader_params = default_params();
+                    }
+                });
+                let previous = menu_state.palette.clone();
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_label("Palette")
+                        .width(128.0)
+                        .selected_text(&menu_state.palette)
+                        .show_ui(ui, |ui| {
+                            for (name, hex) in PALETTES.iter() {
+                                ui.horizontal(|ui| {
+                                    swatches(ui, &Palette::from_hex(hex).unwrap());
+                                    ui.selectable_value(&mut menu_state.palette, name.to_string(), *name);
+                                });
+                            }
+                            ui.selectable_value(&mut menu_state.palette, CUSTOM_PALETTE.to_string(), CUSTOM_PALETTE);
+                        });
+                    swatches(ui, &menu_state.palette());
+                });
+                if menu_state.palette == CUSTOM_PALETTE {
+                    ui.horizontal(|ui| {
+                        for (label, hex) in PALETTE_LABELS.iter().zip(menu_state.custom_palette.iter_mut()) {
+                            ui.label(*label);
+                            ui.add(egui::TextEdit::singleline(hex).desired_width(64.0));
+                        }
+                    });
+                }
+                if menu_state.palette != previous {
+                    menu_state.rom_palettes.insert(menu_state.selected.clone(), menu_state.palette.clone());
+                }
+                ui.separator();
+                ui.checkbox(&mut menu_state.muted, "Mute sound");
+                egui::ComboBox::from_label("Waveform")
+                    .width(128.0)
+                    .selected_text(format!("{:?}", menu_state.waveform))
+                    .show_ui(ui, |ui| {
+                        for waveform in Waveform::ALL.iter() {
+                            ui.selectable_value(&mut menu_state.waveform, *waveform, format!("{:?}", waveform));
+                        }
+                    });
+                ui.add(egui::Slider::new(&mut menu_state.frequency, FREQUENCY_RANGE).text("Beep frequency in Hz"));
+                ui.add(egui::Slider::new(&mut menu_state.volume, VOLUME_RANGE).text("Volume"));
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_label("Screenshots")
+                        .width(128.0)
+                        .selected_text(format!("{:?}", menu_state.screenshot_mode))
+                        .show_ui(ui, |ui| {
+                            for mode in ScreenshotMode::ALL.iter() {
+                                ui.selectable_value(&mut menu_state.screenshot_mode, *mode, format!("{:?}", mode));
+                            }
+                        });
+                    if menu_state.screenshot_mode == ScreenshotMode::Scaled {
+                        ui.add(egui::Slider::new(&mut menu_state.screenshot_scale, SCREENSHOT_SCALE_RANGE).text("Scale"));
+                    }
+                });
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_label("Recordings")
+                        .width(128.0)
+                        .selected_text(format!("{:?}", menu_state.recording_format))
+                        .show_ui(ui, |ui| {
+                            for format in RecordingFormat::ALL.iter() {
+                                ui.selectable_value(&mut menu_state.recording_format, *format, format!("{:?}", format));
+                            }
+                        });
+                    ui.add(egui::Slider::new(&mut menu_state.recording_scale, RECORDING_SCALE_RANGE).text("Scale"));
+                });
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_label("Input movie")
+                        .width(128.0)
+                        .selected_text(format!("{:?}", menu_state.movie_mode))
+                        .show_ui(ui, |ui| {
+                            for mode in MovieMode::ALL.iter() {
+                                ui.selectable_value(&mut menu_state.movie_mode, *mode, format!("{:?}", mode));
+                            }
+                        });
+                    if menu_state.movie_mode == MovieMode::Play {
+                        ui.add(egui::TextEdit::singleline(&mut menu_state.movie_path).desired_width(256.0));
+                    }
+                });
+                ui.label("Recorded movies are saved to movies/ when leaving the game. Playback starts the game the movie was recorded with.");
                 ui.separator();
                 if ui.button("Start!").clicked() {
                     *state = State::InGame(menu_state.selected.clone());
                 }
                 ui.label("Once in game, press Esc to return to the menu.");
+                ui.label("Hold Tab for turbo, press F5 to cycle slow motion, P to pause and N to advance a frame while paused. F9 starts and stops recording audio to a WAV file, F10 recording video, F11 toggles fullscreen and F12 takes a screenshot.");
                 ui.separator();
-                ui.monospace("Controls:\nCHIP-8     Emu\n1 2 3 C    1 2 3 4\n4 5 6 D    Q W E R\n7 8 9 E    A S D F\nA 0 B F    Z/Y X C V");
+                egui::CollapsingHeader::new("Controls").show(ui, |ui| {
+                    let selected = menu_state.selected.clone();
+                    let mut separate = menu_state.rom_controls.contains_key(&selected);
+                    if ui.checkbox(&mut separate, format!("Separate bindings for {}", selected)).changed() {
+                        if separate {
+                            let controls = menu_state.controls.clone();
+                            menu_state.rom_controls.insert(selected.clone(), controls);
+                        } else {
+                            menu_state.rom_controls.remove(&selected);
+                        }
+                    }
+                    let MenuState { controls, rom_controls, binding, .. } = menu_state;
+                    let active = rom_controls.get_mut(&selected).unwrap_or(controls);
+                    ui.horizontal(|ui| {
+                        ui.label("Keyboard layout:");
+                        for (name, _) in LAYOUTS.iter() {
+                            if ui.button(*name).clicked() {
+                                active.keys = Keymap::layout(name).unwrap();
+                            }
+                        }
+                    });
+                    bindings_grid(ui, "keys", &mut active.keys, binding, Binding::Key);
+                    ui.horizontal(|ui| {
+                        ui.label("Gamepad:");
+                        if ui.button("Reset to defaults").clicked() {
+                            active.pad = PadMap::default();
+                        }
+                    });
+                    bindings_grid(ui, "buttons", &mut active.pad, binding, Binding::Button);
+                    ui.label("Bound keys take precedence over the hotkeys. Esc can't be bound and cancels binding.");
+                    ui.horizontal(|ui| {
+                        egui::ComboBox::from_label("Touch keypad")
+                            .width(128.0)
+                            .selected_text(format!("{:?}", menu_state.touch_keypad))
+                            .show_ui(ui, |ui| {
+                                for keypad in TouchKeypad::ALL.iter() {
+                                    ui.selectable_value(&mut menu_state.touch_keypad, *keypad, format!("{:?}", keypad));
+                                }
+                            });
+                        ui.checkbox(&mut menu_state.hide_unused_keys, "Hide keys the game doesn't use");
+                    });
+                });
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("Reset to defaults").clicked() {
+                        reset = true;
+                    }
+                    ui.label("Settings are saved as they change.");
+                });
             });
     });
+    if reset {
+        *menu_state = MenuState::default();
+    }
+    changed
 }
 
-pub fn show_debugger(debugger_state: &mut DebuggerState, cpu: &mut Cpu) {
+fn swatches(ui: &mut egui::Ui, palette: &Palette) {
+    for color in palette.colors[..PALETTE_LABELS.len()].iter() {
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(12.0, 12.0), egui::Sense::hover());
+        let color = egui::Color32::from_rgb(
+            (color.r * 255.0) as u8,
+            (color.g * 255.0) as u8,
+            (color.b * 255.0) as u8,
+        );
+        ui.painter().rect_filled(rect, 0.0, color);
+    }
+}
+
+/// `movie` is set while a movie is being recorded or played back, which single steps would put
+/// out of sync.
+pub fn show_debugger(debugger_state: &mut DebuggerState, cpu: &mut Cpu, movie: bool) {
     egui_macroquad::ui(|egui_ctx| {
         egui::Window::new("Debugger")
             .scroll(true)
             .default_width(500.0)
             .show(egui_ctx, |ui| {
-                ui.checkbox(&mut debugger_state.running, "Run CPU");
+                if let Some(err) = &debugger_state.error {
+                    ui.colored_label(egui::Color32::RED, err.to_string());
+                }
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut debugger_state.running, "Run CPU");
+                    if ui.button("Screenshot").clicked() {
+                        debugger_state.screenshot = true;
+                    }
+                });
                 ui.separator();
-                if !debugger_state.running && ui.button("Step").clicked() {
-                    cpu.step();
+                let step = egui::Button::new("Step").enabled(!movie);
+                if !debugger_state.running && ui.add(step).clicked() {
+                    if let Err(err) = cpu.step() {
+                        debugger_state.halt(err);
+                    }
                     debugger_state.delay_counter += 1;
                     if debugger_state.delay_counter == 7 {
+                        cpu.vblank();
                         cpu.dec_regs();
                         debugger_state.delay_counter = 0;
                     }
@@ -113,7 +630,12 @@ pub fn show_debugger(debugger_state: &mut DebuggerState, cpu: &mut Cpu) {
                 egui::CollapsingHeader::new("Registers")
                     .default_open(true)
                     .show(ui, |ui| {
-                        ui.monospace(get_registers(&cpu));
+                        ui.monospace(get_registers(cpu));
+                    });
+                egui::CollapsingHeader::new("Keypad")
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        show_keypad(ui, cpu);
                     });
                 egui::CollapsingHeader::new("Stack")
                     .default_open(true)
@@ -147,6 +669,69 @@ pub fn show_debugger(debugger_state: &mut DebuggerState, cpu: &mut Cpu) {
     });
 }
 
+/// The CHIP-8 keys laid out like the hex keypad, each with the inputs bound to it. Clicking an input
+/// unbinds it, clicking + waits for a new one.
+fn bindings_grid<T: Copy + PartialEq + Debug>(
+    ui: &mut egui::Ui,
+    id: &str,
+    bindings: &mut Bindings<T>,
+    binding: &mut Option<Binding>,
+    listen: fn(usize) -> Binding,
+) {
+    egui::Grid::new(id).show(ui, |ui| {
+        for row in KEYPAD.iter() {
+            for key in row.iter() {
+                ui.horizontal(|ui| {
+                    ui.monospace(format!("{:X}:", key));
+                    for host in bindings.keys[*key].clone() {
+                        let button = ui.small_button(format!("{:?}", host));
+                        if button.on_hover_text("Click to unbind").clicked() {
+                            bindings.unbind(*key, host);
+                        }
+                    }
+                    let label = if *binding == Some(listen(*key)) {
+                        "Press..."
+                    } else {
+                        "+"
+                    };
+                    if ui.small_button(label).clicked() {
+                        *binding = Some(listen(*key));
+                    }
+                });
+            }
+            ui.end_row();
+        }
+    });
+}
+
+/// The keys held right now, laid out like the hex keypad, and what FX0A is waiting for. The key it
+/// latched is blue, other held keys green.
+fn show_keypad(ui: &mut egui::Ui, cpu: &Cpu) {
+    egui::Grid::new("keypad").show(ui, |ui| {
+        for row in KEYPAD.iter() {
+            for key in row.iter() {
+                let label = egui::Label::new(format!(" {:X} ", key)).monospace();
+                let label = match cpu.key_wait() {
+                    Some(KeyWait::Release { key: latched, .. }) if usize::from(latched) == *key => {
+                        label.background_color(egui::Color32::from_rgb(0, 0, 160))
+                    }
+                    _ if cpu.keymap[*key] => {
+                        label.background_color(egui::Color32::from_rgb(0, 110, 0))
+                    }
+                    _ => label,
+                };
+                ui.add(label);
+            }
+            ui.end_row();
+        }
+    });
+    ui.label(match cpu.key_wait() {
+        None => "Not waiting for input".to_string(),
+        Some(KeyWait::Press { x, .. }) => format!("Blocked until a key is pressed, into V{:X}", x),
+        Some(KeyWait::Release { key, .. }) => format!("Blocked until {:X} is released", key),
+    });
+}
+
 fn get_registers(cpu: &Cpu) -> String {
     let mut label = String::new();
     for (idx, reg) in cpu.regs.iter().enumerate() {
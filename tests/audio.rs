@@ -0,0 +1,131 @@
+use wasm_chip8::audio::{encode_wav, pattern_rate, Beeper, Waveform};
+use wasm_chip8::cpu::Cpu;
+use wasm_chip8::scheduler::Scheduler;
+
+fn frame(beeper: &mut Beeper, sound: u8) -> Vec<f32> {
+    let mut cpu = Cpu::new();
+    cpu.reg_sound = sound;
+    let mut out = Vec::new();
+    beeper.render_frame(&cpu, &mut out);
+    out
+}
+
+#[test]
+fn silent_without_sound_timer() {
+    let mut beeper = Beeper::new(44100);
+    let out = frame(&mut beeper, 0);
+    assert_eq!(out.len(), 735);
+    assert!(out.iter().all(|s| *s == 0.0));
+}
+
+#[test]
+fn frame_lengths_add_up() {
+    // 22050 isn't a multiple of 60, the remainder must carry over
+    let mut beeper = Beeper::new(22050);
+    let total: usize = (0..60).map(|_| frame(&mut beeper, 0).len()).sum();
+    assert_eq!(total, 22050);
+}
+
+#[test]
+fn beeps_at_volume() {
+    for waveform in Waveform::ALL.iter() {
+        let mut beeper = Beeper::new(44100);
+        beeper.waveform = *waveform;
+        beeper.volume = 0.5;
+        let out = frame(&mut beeper, 2);
+        let peak = out.iter().fold(0.0f32, |max, s| max.max(s.abs()));
+        assert!(peak > 0.45 && peak <= 0.5, "{:?}: {}", waveform, peak);
+    }
+}
+
+#[test]
+fn muted() {
+    let mut beeper = Beeper::new(44100);
+    beeper.muted = true;
+    assert!(frame(&mut beeper, 2).iter().all(|s| *s == 0.0));
+}
+
+#[test]
+fn no_clicks() {
+    // A square wave jumps by up to twice its amplitude, so check the envelope at start and stop
+    let mut beeper = Beeper::new(44100);
+    beeper.volume = 1.0;
+    let mut out = frame(&mut beeper, 1);
+    out.extend(frame(&mut beeper, 0));
+
+    assert!(out[0].abs() < 0.01);
+    assert!(out[735].abs() > 0.99);
+    assert!(out[735 + 10].abs() < 1.0);
+    assert_eq!(*out.last().unwrap(), 0.0);
+}
+
+#[test]
+fn scheduler_renders_audio_per_tick() {
+    let mut cpu = Cpu::new();
+    cpu.init_mem(&[0x12, 0x00]).unwrap();
+    cpu.reg_sound = 3;
+
+    let mut scheduler = Scheduler::default();
+    scheduler.audio = Some(Beeper::new(48000));
+    for _ in 0..5 {
+        scheduler.tick(&mut cpu).unwrap();
+    }
+
+    let samples = scheduler.take_samples();
+    assert_eq!(samples.len(), 5 * 800);
+    // Sound for the first 3 frames, then it fades out
+    assert!(samples[..2400].iter().any(|s| *s != 0.0));
+    assert!(samples[3200..].iter().all(|s| *s == 0.0));
+    assert!(scheduler.take_samples().is_empty());
+}
+
+#[test]
+fn xo_chip_pattern() {
+    let mut cpu = Cpu::new();
+    cpu.reg_sound = 1;
+    // Half a pattern on, half off. At pitch 64 that's 4000 bits, so 31.25 cycles, per second
+    let mut pattern = [0; 16];
+    pattern[..8].copy_from_slice(&[0xFF; 8]);
+    cpu.audio_pattern = Some(pattern);
+
+    let mut beeper = Beeper::new(32000);
+    beeper.volume = 1.0;
+    let mut out = Vec::new();
+    for _ in 0..3 {
+        beeper.render_frame(&cpu, &mut out);
+    }
+
+    // 8 samples per bit, the first 512 of which are high (apart from the fade in)
+    assert!(out[1..512].iter().all(|s| *s > 0.0));
+    assert!(out[512..1024].iter().all(|s| *s < 0.0));
+    assert!(out[1024..1030].iter().all(|s| *s > 0.0));
+}
+
+#[test]
+fn pitch_register() {
+    assert_eq!(pattern_rate(64), 4000.0);
+    assert_eq!(pattern_rate(112), 8000.0);
+    assert_eq!(pattern_rate(16), 2000.0);
+}
+
+#[test]
+fn wav_encoding() {
+    let wav = encode_wav(&[0.0, 1.0, -1.0, 2.0], 44100);
+
+    assert_eq!(&wav[..4], b"RIFF");
+    assert_eq!(u32::from_le_bytes([wav[4], wav[5], wav[6], wav[7]]), 36 + 8);
+    assert_eq!(&wav[8..16], b"WAVEfmt ");
+    assert_eq!(
+        u32::from_le_bytes([wav[24], wav[25], wav[26], wav[27]]),
+        44100
+    );
+    assert_eq!(&wav[36..40], b"data");
+    assert_eq!(u32::from_le_bytes([wav[40], wav[41], wav[42], wav[43]]), 8);
+
+    let samples: Vec<i16> = wav[44..]
+        .chunks(2)
+        .map(|b| i16::from_le_bytes([b[0], b[1]]))
+        .collect();
+    // Out of range samples are clipped
+    assert_eq!(samples, [0, i16::MAX, -i16::MAX, i16::MAX]);
+}
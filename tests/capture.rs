@@ -0,0 +1,102 @@
+use macroquad::prelude::{Color, Image, BLACK, WHITE};
+use wasm_chip8::capture::{encode_png, flip_vertical, scale_image, GifRecorder};
+use wasm_chip8::display::preset;
+
+// Survives the round trip through u8 unlike macroquad's RED
+const RED: Color = Color::new(1.0, 0.0, 0.0, 1.0);
+
+fn sample_image() -> Image {
+    let mut img = Image::gen_image_color(3, 2, BLACK);
+    img.set_pixel(0, 0, RED);
+    img.set_pixel(2, 1, WHITE);
+    img
+}
+
+#[test]
+fn png_round_trip() {
+    let img = sample_image();
+    let png = encode_png(&img);
+
+    let decoder = png::Decoder::new(&png[..]);
+    let (info, mut reader) = decoder.read_info().unwrap();
+    assert_eq!((info.width, info.height), (3, 2));
+    assert_eq!(info.color_type, png::ColorType::RGBA);
+
+    let mut bytes = vec![0; info.buffer_size()];
+    reader.next_frame(&mut bytes).unwrap();
+    assert_eq!(bytes, img.bytes);
+}
+
+#[test]
+fn scale() {
+    let scaled = scale_image(&sample_image(), 4);
+    assert_eq!((scaled.width, scaled.height), (12, 8));
+    assert_eq!(scaled.get_pixel(3, 3), RED);
+    assert_eq!(scaled.get_pixel(4, 3), BLACK);
+    assert_eq!(scaled.get_pixel(11, 7), WHITE);
+    assert_eq!(scaled.get_pixel(8, 4), WHITE);
+}
+
+#[test]
+fn flip() {
+    let mut img = sample_image();
+    flip_vertical(&mut img);
+    assert_eq!(img.get_pixel(0, 1), RED);
+    assert_eq!(img.get_pixel(2, 0), WHITE);
+    assert_eq!(img.get_pixel(0, 0), BLACK);
+
+    // Odd heights leave the middle row alone
+    let mut img = Image::gen_image_color(1, 3, BLACK);
+    img.set_pixel(0, 0, RED);
+    img.set_pixel(0, 1, WHITE);
+    flip_vertical(&mut img);
+    assert_eq!(img.get_pixel(0, 2), RED);
+    assert_eq!(img.get_pixel(0, 1), WHITE);
+}
+
+/// Decodes a GIF into its frames' (delay, pixel at (0, 0)).
+fn decode_gif(gif: &[u8], width: u16, height: u16) -> Vec<(u16, u8)> {
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::Indexed);
+    let mut decoder = options.read_info(gif).unwrap();
+    assert_eq!((decoder.width(), decoder.height()), (width, height));
+
+    let mut frames = Vec::new();
+    while let Some(frame) = decoder.read_next_frame().unwrap() {
+        assert_eq!((frame.width, frame.height), (width, height));
+        frames.push((frame.delay, frame.buffer[0]));
+    }
+    frames
+}
+
+#[test]
+fn gif_merges_identical_frames() {
+    let mut recorder = GifRecorder::new(&preset("Classic").unwrap(), 2);
+    let mut fb = [false; 32 * 64];
+    for _ in 0..30 {
+        recorder.push(&fb);
+    }
+    fb[0] = true;
+    for _ in 0..30 {
+        recorder.push(&fb);
+    }
+    assert_eq!(recorder.frames(), 60);
+
+    let frames = decode_gif(&recorder.finish(), 128, 64);
+    assert_eq!(frames, [(50, 0), (50, 1)]);
+}
+
+#[test]
+fn gif_keeps_60hz_timing() {
+    // Flickering every frame is faster than GIFs can go, but the total length stays right
+    let mut recorder = GifRecorder::new(&preset("Classic").unwrap(), 1);
+    let mut fb = [false; 32 * 64];
+    for _ in 0..120 {
+        fb[0] = !fb[0];
+        recorder.push(&fb);
+    }
+
+    let frames = decode_gif(&recorder.finish(), 64, 32);
+    assert!(frames.iter().all(|(delay, _)| *delay >= 2));
+    assert_eq!(frames.iter().map(|(delay, _)| delay).sum::<u16>(), 200);
+}
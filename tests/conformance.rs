@@ -0,0 +1,346 @@
+use std::fs;
+use std::path::PathBuf;
+
+use wasm_chip8::cpu::KeyWait;
+use wasm_chip8::headless::Headless;
+
+// Timendus' combined suite picks a test from the byte at 0x1FF and, for the quirks and keypad
+// tests, a platform or sub-test from the byte at 0x1FE, so no menu navigation is needed.
+const TEST_SUITE: &[u8] = include_bytes!("roms/chip8-test-suite.ch8");
+const BC_TEST: &[u8] = include_bytes!("roms/BC_test.ch8");
+
+const IBM_LOGO: u8 = 1;
+const CORAX_PLUS: u8 = 2;
+const FLAGS: u8 = 3;
+const QUIRKS: u8 = 4;
+const KEYPAD: u8 = 5;
+
+// Sub-tests of the keypad test
+const KEYPAD_DOWN: u8 = 1;
+const KEYPAD_GETKEY: u8 = 3;
+
+struct Profile {
+    name: &'static str,
+    st_compat: bool,
+    sh_compat: bool,
+    display_wait: bool,
+    platform: u8,
+}
+
+const CHIP8: Profile = Profile {
+    name: "chip8",
+    st_compat: false,
+    sh_compat: false,
+    display_wait: true,
+    platform: 1,
+};
+
+const SCHIP: Profile = Profile {
+    name: "schip",
+    st_compat: true,
+    sh_compat: true,
+    display_wait: false,
+    platform: 2,
+};
+
+/// `(frame, key, pressed)`, applied before running the given frame.
+type KeyEvent = (usize, usize, bool);
+
+struct Case<'a> {
+    name: &'a str,
+    rom: &'a [u8],
+    test: Option<u8>,
+    /// Byte at 0x1FE if not the profile's platform
+    option: Option<u8>,
+    frames: usize,
+    input: &'a [KeyEvent],
+}
+
+/// Loads `case` without running it.
+fn start(case: &Case, profile: &Profile) -> Headless {
+    let mut emu = Headless::new(case.rom).unwrap();
+    emu.cpu.st_compat = profile.st_compat;
+    emu.cpu.sh_compat = profile.sh_compat;
+    emu.cpu.display_wait = profile.display_wait;
+
+    if let Some(test) = case.test {
+        emu.cpu.mem[0x1FF] = test;
+        emu.cpu.mem[0x1FE] = case.option.unwrap_or(profile.platform);
+    }
+    emu
+}
+
+fn run(case: &Case, profile: &Profile) -> Headless {
+    let mut emu = start(case, profile);
+    for frame in 0..case.frames {
+        for (_, key, pressed) in case.input.iter().filter(|e| e.0 == frame) {
+            emu.cpu.set_key(*key, *pressed);
+        }
+        emu.run_frame().unwrap();
+    }
+    emu
+}
+
+fn render(fb: &[bool; 32 * 64]) -> String {
+    let mut out = String::new();
+    for row in fb.chunks(64) {
+        out.extend(row.iter().map(|lit| if *lit { '#' } else { '.' }));
+        out.push('\n');
+    }
+    out
+}
+
+fn diff(expected: &str, actual: &str) -> String {
+    let mut out = String::from("   expected");
+    out.push_str(&" ".repeat(57));
+    out.push_str("actual\n");
+
+    for (idx, (e, a)) in expected.lines().zip(actual.lines()).enumerate() {
+        let marker = if e == a { ' ' } else { '!' };
+        out.push_str(format!("{}{:02} {}  {}\n", marker, idx, e, a).as_str());
+    }
+    out
+}
+
+/// Compares the final framebuffer of each profile against `tests/golden/<case>_<profile>.txt`.
+/// Run with `BLESS=1` to (re)write the golden images instead.
+fn check(case: &Case, profiles: &[&Profile]) {
+    for profile in profiles {
+        check_golden(case, profile, run(case, profile).cpu.get_framebuffer());
+    }
+}
+
+fn check_golden(case: &Case, profile: &Profile, fb: &[bool; 32 * 64]) {
+    let actual = render(fb);
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}_{}.txt", case.name, profile.name));
+
+    if std::env::var_os("BLESS").is_some() {
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|_| panic!("missing golden image {}", path.display()));
+
+    if expected != actual {
+        panic!(
+            "{} ({}) does not match {}\n{}",
+            case.name,
+            profile.name,
+            path.display(),
+            diff(&expected, &actual)
+        );
+    }
+}
+
+#[test]
+fn ibm_logo() {
+    let case = Case {
+        name: "ibm_logo",
+        rom: TEST_SUITE,
+        test: Some(IBM_LOGO),
+        option: None,
+        frames: 60,
+        input: &[],
+    };
+    check(&case, &[&CHIP8, &SCHIP]);
+}
+
+#[test]
+fn corax_plus() {
+    let case = Case {
+        name: "corax_plus",
+        rom: TEST_SUITE,
+        test: Some(CORAX_PLUS),
+        option: None,
+        frames: 120,
+        input: &[],
+    };
+    check(&case, &[&CHIP8, &SCHIP]);
+}
+
+#[test]
+fn flags() {
+    let case = Case {
+        name: "flags",
+        rom: TEST_SUITE,
+        test: Some(FLAGS),
+        option: None,
+        frames: 240,
+        input: &[],
+    };
+    check(&case, &[&CHIP8, &SCHIP]);
+}
+
+// The rows of the quirks test, top to bottom
+const QUIRK_ROWS: [&str; 6] = [
+    "VF reset",
+    "memory",
+    "display wait",
+    "clipping",
+    "shifting",
+    "jumping",
+];
+
+// Quirks we don't emulate yet. The test fails once one of them passes, so it gets taken off here.
+// Sprites wrap around the screen edges on every platform, AND/OR/XOR never reset VF, and BNNN
+// always jumps relative to V0.
+const CHIP8_QUIRK_FAILURES: &[&str] = &["VF reset", "clipping"];
+const SCHIP_QUIRK_FAILURES: &[&str] = &["clipping", "jumping"];
+
+/// Whether each row of the quirks test ends in a tick rather than a cross. The mark takes up 3x3
+/// pixels at the right edge of the row.
+fn quirk_results(fb: &[bool; 32 * 64]) -> Vec<bool> {
+    let cross = [
+        [true, false, true],
+        [false, true, false],
+        [true, false, true],
+    ];
+    (0..QUIRK_ROWS.len())
+        .map(|row| {
+            let top = 2 + 5 * row;
+            let mark: Vec<Vec<bool>> = (top..top + 3)
+                .map(|y| (59..62).map(|x| fb[y * 64 + x]).collect())
+                .collect();
+            mark != cross
+        })
+        .collect()
+}
+
+#[test]
+fn quirks() {
+    let case = Case {
+        name: "quirks",
+        rom: TEST_SUITE,
+        test: Some(QUIRKS),
+        option: None,
+        frames: 1200,
+        input: &[],
+    };
+    let profiles: [(&Profile, &[&str]); 2] = [
+        (&CHIP8, CHIP8_QUIRK_FAILURES),
+        (&SCHIP, SCHIP_QUIRK_FAILURES),
+    ];
+    for (profile, failures) in profiles.iter() {
+        let emu = run(&case, profile);
+        let results = quirk_results(emu.cpu.get_framebuffer());
+        for (name, passed) in QUIRK_ROWS.iter().zip(results) {
+            assert_eq!(
+                passed,
+                !failures.contains(name),
+                "{} quirk on {}, expected it to {}\n{}",
+                name,
+                profile.name,
+                if failures.contains(name) {
+                    "fail"
+                } else {
+                    "pass"
+                },
+                render(emu.cpu.get_framebuffer())
+            );
+        }
+    }
+}
+
+#[test]
+fn keypad_down() {
+    // Hold 1, then 5 and A
+    let case = Case {
+        name: "keypad_down",
+        rom: TEST_SUITE,
+        test: Some(KEYPAD),
+        option: Some(KEYPAD_DOWN),
+        frames: 120,
+        input: &[
+            (20, 0x1, true),
+            (30, 0x1, false),
+            (60, 0x5, true),
+            (60, 0xA, true),
+        ],
+    };
+    check(&case, &[&CHIP8]);
+}
+
+/// Runs until FX0A starts waiting for a key, returning the frames that took.
+fn run_until_key_wait(emu: &mut Headless, max_frames: usize) -> usize {
+    for frame in 0..max_frames {
+        if emu.cpu.key_wait().is_some() {
+            return frame;
+        }
+        emu.run_frame().unwrap();
+    }
+    panic!("FX0A wasn't reached in {} frames", max_frames);
+}
+
+#[test]
+fn keypad_getkey() {
+    // Once FX0A waits, press 7 and release it a few frames later. The test fails a key that is
+    // let go of within 3 frames
+    let case = Case {
+        name: "keypad_getkey",
+        rom: TEST_SUITE,
+        test: Some(KEYPAD),
+        option: Some(KEYPAD_GETKEY),
+        frames: 120,
+        input: &[],
+    };
+    let mut emu = start(&case, &CHIP8);
+    let mut frames = run_until_key_wait(&mut emu, case.frames);
+
+    emu.cpu.set_key(0x7, true);
+    emu.run_frames(10).unwrap();
+    emu.cpu.set_key(0x7, false);
+    frames += 10;
+    emu.run_frames(case.frames.saturating_sub(frames)).unwrap();
+    check_golden(&case, &CHIP8, emu.cpu.get_framebuffer());
+}
+
+#[test]
+fn keypad_getkey_waits_for_release() {
+    let case = Case {
+        name: "keypad_getkey",
+        rom: TEST_SUITE,
+        test: Some(KEYPAD),
+        option: Some(KEYPAD_GETKEY),
+        frames: 120,
+        input: &[],
+    };
+    let mut emu = start(&case, &CHIP8);
+    run_until_key_wait(&mut emu, case.frames);
+    let pc = emu.cpu.pc;
+
+    // FX0A takes the key as soon as it's pressed, but holds the program until it's released
+    emu.cpu.set_key(0x7, true);
+    for frame in 0..30 {
+        emu.run_frame().unwrap();
+        assert_eq!(
+            emu.cpu.key_wait(),
+            Some(KeyWait::Release { x: 0, key: 0x7 }),
+            "frame {}",
+            frame
+        );
+        assert_eq!(emu.cpu.pc, pc, "frame {}", frame);
+    }
+
+    emu.cpu.set_key(0x7, false);
+    emu.run_frame().unwrap();
+    assert_ne!(emu.cpu.pc, pc);
+    emu.run_frames(60).unwrap();
+    check_golden(&case, &CHIP8, emu.cpu.get_framebuffer());
+}
+
+#[test]
+fn bc_test() {
+    // BC_test expects the SUPER-CHIP load/store and shift behaviour
+    let case = Case {
+        name: "bc_test",
+        rom: BC_TEST,
+        test: None,
+        option: None,
+        frames: 60,
+        input: &[],
+    };
+    check(&case, &[&SCHIP]);
+}
@@ -0,0 +1,197 @@
+mod reference;
+
+use std::collections::VecDeque;
+
+use reference::{Fault, Reference};
+use wasm_chip8::cpu::{Cpu, CpuError};
+use wasm_chip8::disassembler::generate_disassembly;
+use wasm_chip8::roms::{get_bytes, ROMS};
+
+const SEED: u64 = 0x5EED_C8C8;
+const FRAMES: usize = 3600;
+const STEPS_PER_FRAME: usize = 8;
+const TRACE_LEN: usize = 16;
+
+/// Xorshift, so the input script does not depend on any global RNG state.
+struct Script(u64);
+
+impl Script {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Every few frames release everything or hold one or two random keys.
+    fn keys(&mut self, frame: usize) -> Option<u16> {
+        if !frame.is_multiple_of(6) {
+            return None;
+        }
+        Some(match self.next() % 4 {
+            0 => 0,
+            1 => 1 << (self.next() % 16),
+            _ => 1 << (self.next() % 16) | 1 << (self.next() % 16),
+        })
+    }
+}
+
+/// The reference only says which instruction it halted on, so every error we report for that
+/// instruction is the same fault.
+fn same_fault(err: &CpuError, fault: &Fault, opcode: u16) -> bool {
+    match (err, fault) {
+        (CpuError::PcOutOfBounds(_), Fault::PcOutOfBounds) => true,
+        (CpuError::UnknownInstruction(instr), Fault::Halted(op)) => instr == op,
+        (
+            CpuError::MemOutOfBounds(_) | CpuError::StackOverflow | CpuError::StackUnderflow,
+            Fault::Halted(op),
+        ) => *op == opcode,
+        _ => false,
+    }
+}
+
+fn compare(cpu: &Cpu, reference: &Reference, display: bool) -> Vec<String> {
+    let mut diffs = Vec::new();
+
+    for idx in 0..0x10 {
+        if cpu.regs[idx] != reference.v[idx] {
+            diffs.push(format!(
+                "V{:X}: 0x{:02X} != 0x{:02X}",
+                idx, cpu.regs[idx], reference.v[idx]
+            ));
+        }
+    }
+    if cpu.reg_i != reference.i {
+        diffs.push(format!("I: 0x{:03X} != 0x{:03X}", cpu.reg_i, reference.i));
+    }
+    if cpu.pc != reference.pc {
+        diffs.push(format!("PC: 0x{:03X} != 0x{:03X}", cpu.pc, reference.pc));
+    }
+    if (cpu.reg_delay, cpu.reg_sound) != (reference.dt, reference.st) {
+        diffs.push(format!(
+            "DT/ST: {}/{} != {}/{}",
+            cpu.reg_delay, cpu.reg_sound, reference.dt, reference.st
+        ));
+    }
+
+    // We store the address of the CALL, the reference the address after it
+    let stack: Vec<usize> = cpu.stack.iter().map(|e| e + 2).collect();
+    if stack != reference.stack {
+        diffs.push(format!("stack: {:03X?} != {:03X?}", stack, reference.stack));
+    }
+
+    if display {
+        let fb = cpu.get_framebuffer();
+        let pixels = (0..32 * 64)
+            .filter(|idx| fb[*idx] != reference.pixel(idx % 64, idx / 64))
+            .count();
+        if pixels > 0 {
+            diffs.push(format!("framebuffer: {} pixels differ", pixels));
+        }
+    }
+
+    diffs
+}
+
+fn run(name: &str, st_compat: bool, sh_compat: bool) -> Result<(), String> {
+    let rom = get_bytes(name);
+
+    let mut cpu = Cpu::new();
+    cpu.init_mem(&rom).unwrap();
+    cpu.st_compat = st_compat;
+    cpu.sh_compat = sh_compat;
+
+    let mut reference = Reference::new(&rom);
+    reference.keep_i = st_compat;
+    reference.shift_vx = sh_compat;
+
+    let mut script = Script(SEED);
+    let mut trace = VecDeque::with_capacity(TRACE_LEN);
+
+    for frame in 0..FRAMES {
+        if let Some(keys) = script.keys(frame) {
+            reference.keys = keys;
+            for key in 0..0x10 {
+                cpu.set_key(key, keys & (1 << key) != 0);
+            }
+        }
+
+        for step in 0..STEPS_PER_FRAME {
+            let opcode = reference.opcode();
+            if trace.len() == TRACE_LEN {
+                trace.pop_front();
+            }
+            trace.push_back(cpu.pc);
+
+            let ours = cpu.step();
+            let theirs = reference.step();
+
+            // RND can't be replayed, so check that our value respects the mask and adopt it
+            let mut diffs = Vec::new();
+            if opcode & 0xF000 == 0xC000 && ours.is_ok() {
+                let (x, kk) = (usize::from(opcode >> 8 & 0xF), opcode as u8);
+                if cpu.regs[x] & !kk != 0 {
+                    diffs.push(format!("RND V{:X}: 0x{:02X} exceeds mask", x, cpu.regs[x]));
+                }
+                reference.v[x] = cpu.regs[x];
+            }
+
+            match (&ours, &theirs) {
+                (Ok(()), Ok(())) => {
+                    // Only CLS and DRW touch the display, no need to compare it every step
+                    let display = opcode == 0x00E0 || opcode & 0xF000 == 0xD000;
+                    diffs.extend(compare(&cpu, &reference, display));
+                }
+                (Err(err), Err(fault)) => {
+                    if !same_fault(err, fault, opcode) {
+                        diffs.push(format!("result: {:?} != {:?}", ours, theirs));
+                    }
+                    diffs.extend(compare(&cpu, &reference, true));
+                    if diffs.is_empty() {
+                        return Ok(());
+                    }
+                }
+                _ => diffs.push(format!("result: {:?} != {:?}", ours, theirs)),
+            }
+
+            if !diffs.is_empty() {
+                let trace: String = trace
+                    .iter()
+                    .map(|pc| generate_disassembly(&mut cpu, *pc..pc + 2))
+                    .collect();
+                return Err(format!(
+                    "{} diverged at frame {} step {} (ours != reference)\n  {}\ntrace:\n{}",
+                    name,
+                    frame,
+                    step,
+                    diffs.join("\n  "),
+                    trace
+                ));
+            }
+        }
+
+        cpu.dec_regs();
+        reference.tick_timers();
+    }
+
+    Ok(())
+}
+
+fn run_all(st_compat: bool, sh_compat: bool) {
+    let failures: Vec<String> = ROMS
+        .iter()
+        .filter_map(|name| run(name, st_compat, sh_compat).err())
+        .collect();
+
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+#[test]
+fn bundled_roms() {
+    run_all(false, false);
+}
+
+#[test]
+fn bundled_roms_compat() {
+    run_all(true, true);
+}
@@ -0,0 +1,127 @@
+use macroquad::prelude::*;
+use wasm_chip8::display::{
+    fb_to_img, preset, viewport, Palette, Persistence, Phosphor, ScaleMode, PALETTES,
+};
+
+#[test]
+fn presets_parse() {
+    for (name, hex) in PALETTES.iter() {
+        let palette = preset(name).unwrap();
+        assert_eq!(palette.to_hex(), hex.map(|h| h.to_string()), "{}", name);
+    }
+    assert_eq!(preset("Nonexistent"), None);
+}
+
+#[test]
+fn custom_hex() {
+    let palette = Palette::from_hex(&["102030", "#ff8000", " #000000 ", "#FFFFFF"]).unwrap();
+    assert_eq!(palette.to_hex()[..2], ["#102030", "#FF8000"]);
+
+    assert_eq!(
+        Palette::from_hex(&["#12345", "#000000", "#000000", "#000000"]),
+        None
+    );
+    assert_eq!(
+        Palette::from_hex(&["#GG0000", "#000000", "#000000", "#000000"]),
+        None
+    );
+}
+
+#[test]
+fn fb_to_img_uses_palette() {
+    let palette = preset("Amber").unwrap();
+    let mut fb = [false; 32 * 64];
+    fb[64 + 3] = true;
+
+    let mut img = Image::gen_image_color(64, 32, BLACK);
+    let mut phosphor = Phosphor::new(Persistence::Off, 0.0);
+    fb_to_img(&mut img, &fb, &fb, &mut phosphor, &palette, 1);
+
+    assert_eq!(img.get_pixel(3, 1), palette.colors[1]);
+    assert_eq!(img.get_pixel(0, 0), palette.colors[0]);
+}
+
+/// Lights (0, 0) for the first frame only, returning its intensity over the following frames.
+fn fade_out(mode: Persistence, decay: f32) -> Vec<f32> {
+    let mut img = Image::gen_image_color(64, 32, BLACK);
+    let mut phosphor = Phosphor::new(mode, decay);
+    let palette = preset("Classic").unwrap();
+
+    let mut fb = [false; 32 * 64];
+    fb[0] = true;
+    fb_to_img(&mut img, &fb, &[false; 32 * 64], &mut phosphor, &palette, 1);
+    let mut previous = fb;
+    fb[0] = false;
+
+    (0..4)
+        .map(|_| {
+            fb_to_img(&mut img, &fb, &previous, &mut phosphor, &palette, 1);
+            previous = fb;
+            phosphor.intensity(0, 0)
+        })
+        .collect()
+}
+
+#[test]
+fn persistence_off() {
+    assert_eq!(fade_out(Persistence::Off, 0.5), [0.0; 4]);
+}
+
+#[test]
+fn persistence_decay() {
+    assert_eq!(
+        fade_out(Persistence::Decay, 0.5),
+        [0.5, 0.25, 0.125, 0.0625]
+    );
+}
+
+#[test]
+fn persistence_or_last_two() {
+    assert_eq!(fade_out(Persistence::OrLastTwo, 0.5), [1.0, 0.0, 0.0, 0.0]);
+}
+
+#[test]
+fn decay_counts_emulated_frames() {
+    let mut img = Image::gen_image_color(64, 32, BLACK);
+    let mut phosphor = Phosphor::new(Persistence::Decay, 0.5);
+    let palette = preset("Classic").unwrap();
+
+    let mut fb = [false; 32 * 64];
+    fb[0] = true;
+    fb_to_img(&mut img, &fb, &fb, &mut phosphor, &palette, 1);
+    fb[0] = false;
+
+    // Rendering without emulating, e.g. while paused, must not fade anything
+    fb_to_img(&mut img, &fb, &fb, &mut phosphor, &palette, 0);
+    assert_eq!(phosphor.intensity(0, 0), 1.0);
+    fb_to_img(&mut img, &fb, &fb, &mut phosphor, &palette, 3);
+    assert_eq!(phosphor.intensity(0, 0), 0.125);
+    assert!((img.get_pixel(0, 0).r - 0.125).abs() < 0.01);
+}
+
+#[test]
+fn viewport_modes() {
+    let source = vec2(68.0, 36.0);
+    let screen = vec2(800.0, 600.0);
+
+    // 11x is the largest that fits 800 wide, centered with the leftover split evenly
+    assert_eq!(
+        viewport(ScaleMode::Integer, source, screen),
+        Rect::new(26.0, 102.0, 748.0, 396.0)
+    );
+    let fit = viewport(ScaleMode::Fit, source, screen);
+    assert_eq!((fit.x, fit.w), (0.0, 800.0));
+    assert!((fit.w / fit.h - 68.0 / 36.0).abs() < 1e-4);
+    assert_eq!(fit.y, ((600.0 - fit.h) / 2.0).floor());
+    assert_eq!(
+        viewport(ScaleMode::Stretch, source, screen),
+        Rect::new(0.0, 0.0, 800.0, 600.0)
+    );
+}
+
+#[test]
+fn integer_viewport_in_small_window() {
+    let rect = viewport(ScaleMode::Integer, vec2(64.0, 32.0), vec2(50.0, 50.0));
+    assert_eq!((rect.w, rect.h), (64.0, 32.0));
+    assert_eq!((rect.x, rect.y), (-7.0, 9.0));
+}
@@ -0,0 +1,126 @@
+use macroquad::prelude::{vec2, KeyCode, Rect};
+use wasm_chip8::keymap::{keypad_buttons, Button, Keymap, PadMap, LAYOUTS};
+use wasm_chip8::roms::{used_keys, ROMS};
+
+#[test]
+fn layouts_bind_every_key_once() {
+    for (name, _) in LAYOUTS.iter() {
+        let keymap = Keymap::layout(name).unwrap();
+        let mut hosts: Vec<KeyCode> = keymap.keys.iter().flatten().copied().collect();
+        assert_eq!(hosts.len(), 16, "{}", name);
+        hosts.sort_by_key(|host| *host as u32);
+        hosts.dedup();
+        assert_eq!(hosts.len(), 16, "{}", name);
+    }
+}
+
+#[test]
+fn default_is_qwerty() {
+    let keymap = Keymap::default();
+    let qwerty = Keymap::layout("QWERTY").unwrap();
+    for key in (0..16).filter(|key| *key != 0xA) {
+        assert_eq!(keymap.keys[key], qwerty.keys[key]);
+    }
+    assert_eq!(keymap.keys[0x0], [KeyCode::X]);
+    assert_eq!(keymap.keys[0xC], [KeyCode::Key4]);
+    // Y stays bound next to Z, as it was before layouts could be picked
+    assert_eq!(keymap.keys[0xA], [KeyCode::Z, KeyCode::Y]);
+    assert_eq!(qwerty.keys[0xA], [KeyCode::Z]);
+    assert_eq!(Keymap::layout("QWERTZ").unwrap().keys[0xA], [KeyCode::Y]);
+    assert!(Keymap::layout("Colemak").is_none());
+}
+
+#[test]
+fn several_host_keys() {
+    let mut keymap = Keymap::default();
+    keymap.bind(0x5, KeyCode::Up);
+    let pressed = keymap.pressed(|host| host == KeyCode::Up);
+    assert_eq!(pressed.iter().filter(|p| **p).count(), 1);
+    assert!(pressed[0x5]);
+    assert!(keymap.pressed(|host| host == KeyCode::W)[0x5]);
+
+    keymap.unbind(0x5, KeyCode::Up);
+    assert!(!keymap.is_bound(KeyCode::Up));
+    assert!(!keymap.pressed(|host| host == KeyCode::Up)[0x5]);
+}
+
+#[test]
+fn binding_moves_host_key() {
+    let mut keymap = Keymap::default();
+    keymap.bind(0x1, KeyCode::X);
+    assert!(keymap.keys[0x0].is_empty());
+    assert_eq!(keymap.keys[0x1], [KeyCode::Key1, KeyCode::X]);
+    assert!(keymap.is_bound(KeyCode::X));
+}
+
+#[test]
+fn gamepad_defaults() {
+    let mut padmap = PadMap::default();
+    let pressed = padmap.pressed(|button| button == Button::StickLeft);
+    assert!(pressed[0x4]);
+    assert_eq!(pressed.iter().filter(|p| **p).count(), 1);
+    assert!(padmap.pressed(|button| button == Button::DPadLeft)[0x4]);
+    assert!(padmap.pressed(|button| button == Button::South)[0x5]);
+
+    padmap.bind(0xF, Button::South);
+    assert!(padmap.keys[0x5].is_empty());
+    assert!(padmap.pressed(|button| button == Button::South)[0xF]);
+}
+
+#[test]
+fn full_keypad() {
+    let buttons = keypad_buttons(Rect::new(0.0, 100.0, 400.0, 200.0), None);
+    assert_eq!(buttons.len(), 16);
+    // Square buttons filling the height, centered
+    let (key, rect) = buttons[0];
+    assert_eq!(key, 0x1);
+    assert!((rect.w - rect.h).abs() < 1e-4);
+    assert!(rect.x > 100.0 && rect.x < 105.0);
+    assert!(rect.y > 100.0 && rect.y < 105.0);
+    let (key, rect) = buttons[15];
+    assert_eq!(key, 0xF);
+    assert!(rect.right() > 295.0 && rect.right() < 300.0);
+    assert!(rect.contains(vec2(280.0, 280.0)));
+}
+
+#[test]
+fn keypad_leaves_out_unused_rows_and_columns() {
+    // PONG's keys are the corners of the top two rows
+    let buttons = keypad_buttons(Rect::new(0.0, 0.0, 100.0, 100.0), used_keys("PONG"));
+    let keys: Vec<usize> = buttons.iter().map(|(key, _)| *key).collect();
+    assert_eq!(keys, [0x1, 0xC, 0x4, 0xD]);
+    assert!(buttons[0].1.w > 40.0);
+    assert!(buttons[1].1.x > buttons[0].1.x);
+    assert!(buttons[2].1.y > buttons[0].1.y);
+
+    // A key missing from a column that's still used leaves a gap
+    let buttons = keypad_buttons(Rect::new(0.0, 0.0, 300.0, 200.0), Some(&[0x2, 0x4, 0x6]));
+    let keys: Vec<usize> = buttons.iter().map(|(key, _)| *key).collect();
+    assert_eq!(keys, [0x2, 0x4, 0x6]);
+    // 100 pixel cells with 5% gaps
+    assert_eq!(buttons[0].1, Rect::new(105.0, 5.0, 90.0, 90.0));
+    assert_eq!(buttons[1].1.x, 5.0);
+    assert_eq!(buttons[2].1.x, 205.0);
+
+    assert!(keypad_buttons(Rect::new(0.0, 0.0, 100.0, 100.0), used_keys("MAZE")).is_empty());
+}
+
+#[test]
+fn rom_key_metadata() {
+    for rom in ROMS.iter() {
+        if let Some(keys) = used_keys(rom) {
+            assert!(keys.iter().all(|key| *key < 16), "{}", rom);
+        }
+    }
+    assert_eq!(used_keys("BRIX"), Some(&[0x4, 0x6][..]));
+    assert_eq!(used_keys("15PUZZLE"), None);
+}
+
+#[test]
+fn unknown_names_are_dropped() {
+    let mut names = vec![vec!["Q", "NoSuchKey"]];
+    names.resize(16, vec![]);
+    let keymap: Keymap = toml::Value::try_from(names).unwrap().try_into().unwrap();
+    assert_eq!(keymap.keys[0], vec![KeyCode::Q]);
+    assert!(keymap.keys[1..].iter().all(Vec::is_empty));
+}
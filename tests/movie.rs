@@ -0,0 +1,133 @@
+use wasm_chip8::headless::Headless;
+use wasm_chip8::movie::{Frame, Movie, MovieError, MovieState, CHECKSUM_INTERVAL};
+use wasm_chip8::roms;
+
+const FRAMES: usize = 600;
+
+/// Plays TETRIS for ten seconds, moving and turning pieces in a fixed pattern.
+fn record() -> Movie {
+    let rom = roms::get_bytes("TETRIS");
+    let mut movie = Movie::new("TETRIS", &rom, 0x1234_5678_9ABC_DEF0, 480);
+    movie.st_compat = true;
+    let (cpu, mut scheduler) = movie.start(&rom).unwrap();
+    scheduler.movie = Some(MovieState::Recording(movie));
+    let mut headless = Headless { cpu, scheduler };
+
+    for frame in 0..FRAMES {
+        let key = [0x4, 0x5, 0x6, 0x7][frame / 20 % 4];
+        for k in 0..16 {
+            headless.cpu.set_key(k, k == key && frame % 20 < 5);
+        }
+        headless.run_frame().unwrap();
+    }
+    match headless.scheduler.movie {
+        Some(MovieState::Recording(movie)) => movie,
+        _ => unreachable!(),
+    }
+}
+
+/// Plays `movie` back to the end, returning the first frame out of sync.
+fn play(movie: Movie) -> Option<usize> {
+    let (cpu, mut scheduler) = movie.start(&roms::get_bytes(&movie.rom)).unwrap();
+    scheduler.movie = Some(MovieState::play(movie));
+    let mut headless = Headless { cpu, scheduler };
+    while !headless.scheduler.movie.as_ref().unwrap().finished() {
+        // Live input is overridden by the movie
+        headless.cpu.set_key(0x5, true);
+        headless.run_frame().unwrap();
+    }
+    headless.scheduler.movie.unwrap().desync()
+}
+
+#[test]
+fn records_keys_and_checksums() {
+    let movie = record();
+    assert_eq!(movie.frames.len(), FRAMES);
+    assert_eq!(movie.frames[0].keys, 1 << 0x4);
+    assert_eq!(movie.frames[5].keys, 0);
+    assert_eq!(movie.frames[20].keys, 1 << 0x5);
+
+    for (idx, frame) in movie.frames.iter().enumerate() {
+        let expected = (idx + 1) % CHECKSUM_INTERVAL == 0 || idx == FRAMES - 1;
+        assert_eq!(frame.checksum.is_some(), expected, "frame {}", idx);
+    }
+}
+
+#[test]
+fn last_frame_always_has_checksum() {
+    let rom = roms::get_bytes("MAZE");
+    let movie = Movie::new("MAZE", &rom, 1, 480);
+    let (cpu, mut scheduler) = movie.start(&rom).unwrap();
+    scheduler.movie = Some(MovieState::Recording(movie));
+    let mut headless = Headless { cpu, scheduler };
+    headless.run_frames(CHECKSUM_INTERVAL + 3).unwrap();
+
+    let movie = headless.scheduler.movie.unwrap();
+    let frames = &movie.movie().frames;
+    assert!(frames[CHECKSUM_INTERVAL - 1].checksum.is_some());
+    assert!(frames[CHECKSUM_INTERVAL + 1].checksum.is_none());
+    assert!(frames[CHECKSUM_INTERVAL + 2].checksum.is_some());
+}
+
+#[test]
+fn text_round_trip() {
+    let movie = record();
+    let text = movie.to_text();
+    assert!(text.starts_with("CHIP-8 movie 1\nrom TETRIS\n"));
+    assert!(text.contains("\nquirks load-store\n"));
+    assert_eq!(Movie::parse(&text), Ok(movie));
+}
+
+#[test]
+fn rejects_malformed_files() {
+    assert_eq!(Movie::parse(""), Err(MovieError::Format(1)));
+    assert_eq!(
+        Movie::parse("CHIP-8 movie 1\nrom X"),
+        Err(MovieError::Format(3))
+    );
+
+    let text = record().to_text();
+    assert_eq!(
+        Movie::parse(&text.replace("seed ", "seed x")),
+        Err(MovieError::Format(4))
+    );
+    assert_eq!(
+        Movie::parse(&format!("{}zz\n", text)),
+        Err(MovieError::Format(8 + FRAMES))
+    );
+}
+
+#[test]
+fn rejects_other_rom() {
+    let movie = record();
+    assert_eq!(
+        movie.start(&roms::get_bytes("BRIX")).err(),
+        Some(MovieError::RomMismatch)
+    );
+}
+
+#[test]
+fn plays_back_in_sync() {
+    assert_eq!(play(record()), None);
+}
+
+#[test]
+fn detects_desync() {
+    let mut movie = record();
+    // Hold a key the recording didn't, on a frame the game doesn't ignore input on
+    for frame in movie.frames[100..110].iter_mut() {
+        *frame = Frame {
+            keys: 1 << 0x6,
+            ..*frame
+        };
+    }
+    let desync = play(movie).expect("tampered input went unnoticed");
+    assert!(desync >= 100, "desync at {}", desync);
+}
+
+#[test]
+fn seed_matters() {
+    let mut movie = record();
+    movie.seed ^= 1;
+    assert!(play(movie).is_some());
+}
@@ -0,0 +1,807 @@
+mod support;
+
+use support::{lit, lit_count, CpuTest, ALL_QUIRKS};
+use wasm_chip8::cpu::{Cpu, CpuError, KeyWait};
+
+#[test]
+fn cls_00e0() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0x00E0)
+            .quirks(quirks)
+            .pixel(0, 0)
+            .pixel(63, 31)
+            .step();
+        assert_eq!(lit_count(&cpu), 0);
+        assert_eq!(cpu.pc, 0x202);
+    }
+}
+
+#[test]
+fn ret_00ee() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0x00EE)
+            .quirks(quirks)
+            .stack(&[0x300, 0x456])
+            .step();
+        assert_eq!(cpu.pc, 0x458);
+        assert_eq!(cpu.stack, vec![0x300]);
+    }
+}
+
+#[test]
+fn compat_00fa() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0x00FA).quirks(quirks).step();
+        assert!(cpu.st_compat);
+        assert_eq!(cpu.sh_compat, quirks.sh_compat);
+        assert_eq!(cpu.pc, 0x202);
+    }
+}
+
+#[test]
+fn jp_1nnn() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0x1ABC).quirks(quirks).step();
+        assert_eq!(cpu.pc, 0xABC);
+        assert!(cpu.stack.is_empty());
+    }
+}
+
+#[test]
+fn call_2nnn() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0x2ABC).quirks(quirks).pc(0x250).step();
+        assert_eq!(cpu.pc, 0xABC);
+        assert_eq!(cpu.stack, vec![0x250]);
+    }
+}
+
+#[test]
+fn call_and_ret_round_trip() {
+    let mut cpu = CpuTest::new(0x2400).build();
+    cpu.mem[0x400] = 0x00;
+    cpu.mem[0x401] = 0xEE;
+    cpu.step().unwrap();
+    cpu.step().unwrap();
+    assert_eq!(cpu.pc, 0x202);
+    assert!(cpu.stack.is_empty());
+}
+
+#[test]
+fn se_3xkk() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0x3A42).quirks(quirks).reg(0xA, 0x42).step();
+        assert_eq!(cpu.pc, 0x204);
+        let cpu = CpuTest::new(0x3A42).quirks(quirks).reg(0xA, 0x41).step();
+        assert_eq!(cpu.pc, 0x202);
+    }
+}
+
+#[test]
+fn sne_4xkk() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0x4A42).quirks(quirks).reg(0xA, 0x42).step();
+        assert_eq!(cpu.pc, 0x202);
+        let cpu = CpuTest::new(0x4A42).quirks(quirks).reg(0xA, 0x41).step();
+        assert_eq!(cpu.pc, 0x204);
+    }
+}
+
+#[test]
+fn se_5xy0() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0x5120)
+            .quirks(quirks)
+            .reg(1, 7)
+            .reg(2, 7)
+            .step();
+        assert_eq!(cpu.pc, 0x204);
+        let cpu = CpuTest::new(0x5120)
+            .quirks(quirks)
+            .reg(1, 7)
+            .reg(2, 8)
+            .step();
+        assert_eq!(cpu.pc, 0x202);
+    }
+}
+
+#[test]
+fn ld_6xkk() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0x6E99).quirks(quirks).step();
+        assert_eq!(cpu.regs[0xE], 0x99);
+        assert_eq!(cpu.pc, 0x202);
+    }
+}
+
+#[test]
+fn add_7xkk_wraps_without_flag() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0x7302)
+            .quirks(quirks)
+            .reg(3, 0xFF)
+            .reg(0xF, 0x55)
+            .step();
+        assert_eq!(cpu.regs[3], 0x01);
+        assert_eq!(cpu.regs[0xF], 0x55);
+        assert_eq!(cpu.pc, 0x202);
+    }
+}
+
+#[test]
+fn ld_8xy0() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0x8120).quirks(quirks).reg(2, 0x33).step();
+        assert_eq!(cpu.regs[1], 0x33);
+        assert_eq!(cpu.regs[2], 0x33);
+        assert_eq!(cpu.pc, 0x202);
+    }
+}
+
+#[test]
+fn or_8xy1() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0x8121)
+            .quirks(quirks)
+            .reg(1, 0b1100)
+            .reg(2, 0b1010)
+            .step();
+        assert_eq!(cpu.regs[1], 0b1110);
+        assert_eq!(cpu.pc, 0x202);
+    }
+}
+
+#[test]
+fn and_8xy2() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0x8122)
+            .quirks(quirks)
+            .reg(1, 0b1100)
+            .reg(2, 0b1010)
+            .step();
+        assert_eq!(cpu.regs[1], 0b1000);
+        assert_eq!(cpu.pc, 0x202);
+    }
+}
+
+#[test]
+fn xor_8xy3() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0x8123)
+            .quirks(quirks)
+            .reg(1, 0b1100)
+            .reg(2, 0b1010)
+            .step();
+        assert_eq!(cpu.regs[1], 0b0110);
+        assert_eq!(cpu.pc, 0x202);
+    }
+}
+
+#[test]
+fn add_8xy4() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0x8124)
+            .quirks(quirks)
+            .reg(1, 0xF0)
+            .reg(2, 0x20)
+            .step();
+        assert_eq!(cpu.regs[1], 0x10);
+        assert_eq!(cpu.regs[0xF], 1);
+
+        let cpu = CpuTest::new(0x8124)
+            .quirks(quirks)
+            .reg(1, 0x10)
+            .reg(2, 0x20)
+            .reg(0xF, 1)
+            .step();
+        assert_eq!(cpu.regs[1], 0x30);
+        assert_eq!(cpu.regs[0xF], 0);
+        assert_eq!(cpu.pc, 0x202);
+    }
+}
+
+#[test]
+fn add_8xy4_vf_as_operand() {
+    for quirks in ALL_QUIRKS {
+        // The flag is written after the result, so it wins when VF is the destination
+        let cpu = CpuTest::new(0x8F14)
+            .quirks(quirks)
+            .reg(0xF, 0xFF)
+            .reg(1, 0x02)
+            .step();
+        assert_eq!(cpu.regs[0xF], 1);
+
+        let cpu = CpuTest::new(0x81F4)
+            .quirks(quirks)
+            .reg(1, 0x02)
+            .reg(0xF, 0x03)
+            .step();
+        assert_eq!(cpu.regs[1], 0x05);
+        assert_eq!(cpu.regs[0xF], 0);
+    }
+}
+
+#[test]
+fn sub_8xy5() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0x8125)
+            .quirks(quirks)
+            .reg(1, 0x30)
+            .reg(2, 0x10)
+            .step();
+        assert_eq!(cpu.regs[1], 0x20);
+        assert_eq!(cpu.regs[0xF], 1);
+
+        let cpu = CpuTest::new(0x8125)
+            .quirks(quirks)
+            .reg(1, 0x10)
+            .reg(2, 0x30)
+            .reg(0xF, 1)
+            .step();
+        assert_eq!(cpu.regs[1], 0xE0);
+        assert_eq!(cpu.regs[0xF], 0);
+
+        // Equal operands do not borrow
+        let cpu = CpuTest::new(0x8125)
+            .quirks(quirks)
+            .reg(1, 0x10)
+            .reg(2, 0x10)
+            .step();
+        assert_eq!(cpu.regs[1], 0);
+        assert_eq!(cpu.regs[0xF], 1);
+    }
+}
+
+#[test]
+fn shr_8xy6() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0x8126)
+            .quirks(quirks)
+            .reg(1, 0b0000_0011)
+            .reg(2, 0b1000_0100)
+            .reg(0xF, 1)
+            .step();
+        if quirks.sh_compat {
+            assert_eq!(cpu.regs[1], 0b0000_0001);
+            assert_eq!(cpu.regs[0xF], 1);
+        } else {
+            assert_eq!(cpu.regs[1], 0b0100_0010);
+            assert_eq!(cpu.regs[0xF], 0);
+        }
+        assert_eq!(cpu.regs[2], 0b1000_0100);
+        assert_eq!(cpu.pc, 0x202);
+    }
+}
+
+#[test]
+fn subn_8xy7() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0x8127)
+            .quirks(quirks)
+            .reg(1, 0x10)
+            .reg(2, 0x30)
+            .step();
+        assert_eq!(cpu.regs[1], 0x20);
+        assert_eq!(cpu.regs[0xF], 1);
+
+        let cpu = CpuTest::new(0x8127)
+            .quirks(quirks)
+            .reg(1, 0x30)
+            .reg(2, 0x10)
+            .reg(0xF, 1)
+            .step();
+        assert_eq!(cpu.regs[1], 0xE0);
+        assert_eq!(cpu.regs[0xF], 0);
+        assert_eq!(cpu.pc, 0x202);
+    }
+}
+
+#[test]
+fn shl_8xye() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0x812E)
+            .quirks(quirks)
+            .reg(1, 0b1100_0000)
+            .reg(2, 0b0100_0001)
+            .reg(0xF, 1)
+            .step();
+        if quirks.sh_compat {
+            assert_eq!(cpu.regs[1], 0b1000_0000);
+            assert_eq!(cpu.regs[0xF], 1);
+        } else {
+            assert_eq!(cpu.regs[1], 0b1000_0010);
+            assert_eq!(cpu.regs[0xF], 0);
+        }
+        assert_eq!(cpu.regs[2], 0b0100_0001);
+        assert_eq!(cpu.pc, 0x202);
+    }
+}
+
+#[test]
+fn shifts_vf_as_operand() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0x8FF6).quirks(quirks).reg(0xF, 0b11).step();
+        assert_eq!(cpu.regs[0xF], 1);
+        let cpu = CpuTest::new(0x8FFE).quirks(quirks).reg(0xF, 0x40).step();
+        assert_eq!(cpu.regs[0xF], 0);
+    }
+}
+
+#[test]
+fn sne_9xy0() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0x9120)
+            .quirks(quirks)
+            .reg(1, 7)
+            .reg(2, 7)
+            .step();
+        assert_eq!(cpu.pc, 0x202);
+        let cpu = CpuTest::new(0x9120)
+            .quirks(quirks)
+            .reg(1, 7)
+            .reg(2, 8)
+            .step();
+        assert_eq!(cpu.pc, 0x204);
+    }
+}
+
+#[test]
+fn ld_annn() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0xA123).quirks(quirks).step();
+        assert_eq!(cpu.reg_i, 0x123);
+        assert_eq!(cpu.pc, 0x202);
+    }
+}
+
+#[test]
+fn jp_bnnn() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0xB300)
+            .quirks(quirks)
+            .reg(0, 0x20)
+            .reg(3, 0x40)
+            .step();
+        assert_eq!(cpu.pc, 0x320);
+    }
+}
+
+#[test]
+fn rnd_cxkk() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0xC500).quirks(quirks).reg(5, 0xFF).step();
+        assert_eq!(cpu.regs[5], 0);
+        assert_eq!(cpu.pc, 0x202);
+
+        for _ in 0..32 {
+            let cpu = CpuTest::new(0xC50F).quirks(quirks).step();
+            assert_eq!(cpu.regs[5] & 0xF0, 0);
+        }
+    }
+}
+
+#[test]
+fn rnd_cxkk_follows_seed() {
+    // C0FF; JP 0x200
+    let sequence = |seed| {
+        let mut cpu = Cpu::new();
+        cpu.init_mem(&[0xC0, 0xFF, 0x12, 0x00]).unwrap();
+        cpu.seed_rng(seed);
+        (0..16)
+            .map(|_| {
+                cpu.step().unwrap();
+                cpu.step().unwrap();
+                cpu.regs[0]
+            })
+            .collect::<Vec<u8>>()
+    };
+    assert_eq!(sequence(7), sequence(7));
+    assert_ne!(sequence(7), sequence(8));
+    // The full range, not just up to 0xFE
+    assert!((0..64).any(|seed| sequence(seed).contains(&0xFF)));
+}
+
+#[test]
+fn drw_dxyn() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0xD122)
+            .quirks(quirks)
+            .reg(1, 4)
+            .reg(2, 3)
+            .i(0x300)
+            .mem(0x300, &[0b1000_0001, 0b0100_0000])
+            .step();
+        assert!(lit(&cpu, 4, 3));
+        assert!(lit(&cpu, 11, 3));
+        assert!(lit(&cpu, 5, 4));
+        assert_eq!(lit_count(&cpu), 3);
+        assert_eq!(cpu.regs[0xF], 0);
+        assert_eq!(cpu.pc, 0x202);
+    }
+}
+
+#[test]
+fn drw_dxyn_collision() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0xD121)
+            .quirks(quirks)
+            .reg(1, 10)
+            .reg(2, 10)
+            .i(0x300)
+            .mem(0x300, &[0b1100_0000])
+            .pixel(10, 10)
+            .step();
+        assert!(!lit(&cpu, 10, 10));
+        assert!(lit(&cpu, 11, 10));
+        assert_eq!(cpu.regs[0xF], 1);
+    }
+}
+
+#[test]
+fn drw_dxyn_wraps_around() {
+    for quirks in ALL_QUIRKS {
+        // Coordinates close to u8::MAX must not overflow before wrapping
+        let cpu = CpuTest::new(0xD122)
+            .quirks(quirks)
+            .reg(1, 0xFF)
+            .reg(2, 0xFF)
+            .i(0x300)
+            .mem(0x300, &[0b1100_0000, 0b1100_0000])
+            .step();
+        assert!(lit(&cpu, 63, 31));
+        assert!(lit(&cpu, 0, 31));
+        assert!(lit(&cpu, 63, 0));
+        assert!(lit(&cpu, 0, 0));
+        assert_eq!(lit_count(&cpu), 4);
+
+        let cpu = CpuTest::new(0xD121)
+            .quirks(quirks)
+            .reg(1, 62)
+            .reg(2, 0)
+            .i(0x300)
+            .mem(0x300, &[0xFF])
+            .step();
+        assert!(lit(&cpu, 62, 0));
+        assert!(lit(&cpu, 63, 0));
+        assert!(lit(&cpu, 5, 0));
+        assert_eq!(lit_count(&cpu), 8);
+    }
+}
+
+#[test]
+fn drw_dxyn_display_wait() {
+    for quirks in ALL_QUIRKS.iter().filter(|quirks| quirks.display_wait) {
+        let mut cpu = CpuTest::new(0xD011)
+            .quirks(*quirks)
+            .i(0x300)
+            .mem(0x300, &[0x80])
+            .build();
+
+        // A frame boundary from before the DXYN doesn't count
+        cpu.vblank();
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert!(cpu.waiting_for_vblank());
+        assert_eq!(lit_count(&cpu), 0);
+        assert_eq!(cpu.pc, 0x200);
+
+        cpu.vblank();
+        cpu.step().unwrap();
+        assert!(!cpu.waiting_for_vblank());
+        assert!(lit(&cpu, 0, 0));
+        assert_eq!(cpu.pc, 0x202);
+    }
+}
+
+#[test]
+fn drw_dxyn_vf_as_coordinate() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0xDFF1)
+            .quirks(quirks)
+            .reg(0xF, 3)
+            .i(0x300)
+            .mem(0x300, &[0x80])
+            .step();
+        assert!(lit(&cpu, 3, 3));
+        assert_eq!(cpu.regs[0xF], 0);
+    }
+}
+
+#[test]
+fn skp_ex9e() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0xE39E)
+            .quirks(quirks)
+            .reg(3, 0xB)
+            .key(0xB)
+            .step();
+        assert_eq!(cpu.pc, 0x204);
+        let cpu = CpuTest::new(0xE39E)
+            .quirks(quirks)
+            .reg(3, 0xB)
+            .key(0xC)
+            .step();
+        assert_eq!(cpu.pc, 0x202);
+    }
+}
+
+#[test]
+fn sknp_exa1() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0xE3A1)
+            .quirks(quirks)
+            .reg(3, 0xB)
+            .key(0xB)
+            .step();
+        assert_eq!(cpu.pc, 0x202);
+        let cpu = CpuTest::new(0xE3A1)
+            .quirks(quirks)
+            .reg(3, 0xB)
+            .key(0xC)
+            .step();
+        assert_eq!(cpu.pc, 0x204);
+    }
+}
+
+#[test]
+fn ld_fx07() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0xF407).quirks(quirks).delay(0x3C).step();
+        assert_eq!(cpu.regs[4], 0x3C);
+        assert_eq!(cpu.pc, 0x202);
+    }
+}
+
+#[test]
+fn ld_fx0a() {
+    for quirks in ALL_QUIRKS {
+        let mut cpu = CpuTest::new(0xF40A).quirks(quirks).build();
+
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc, 0x200);
+
+        cpu.set_key(0x6, true);
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc, 0x200);
+        assert_eq!(cpu.regs[4], 0x6);
+
+        cpu.set_key(0x6, false);
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc, 0x202);
+        assert_eq!(cpu.regs[4], 0x6);
+    }
+}
+
+#[test]
+fn ld_fx0a_latches_first_new_key() {
+    for quirks in ALL_QUIRKS {
+        let mut cpu = CpuTest::new(0xF40A).quirks(quirks).build();
+        // Held before the wait started, doesn't count until pressed again
+        cpu.set_key(0x2, true);
+        cpu.step().unwrap();
+        assert_eq!(
+            cpu.key_wait(),
+            Some(KeyWait::Press {
+                x: 4,
+                held: 1 << 0x2
+            })
+        );
+
+        cpu.set_key(0x9, true);
+        cpu.step().unwrap();
+        assert_eq!(cpu.key_wait(), Some(KeyWait::Release { x: 4, key: 0x9 }));
+        assert_eq!(cpu.regs[4], 0x9);
+
+        // A lower key pressed later doesn't replace it
+        cpu.set_key(0x7, true);
+        cpu.step().unwrap();
+        assert_eq!(cpu.regs[4], 0x9);
+        assert_eq!(cpu.pc, 0x200);
+
+        // Releasing the latched key is enough, the others may stay down
+        cpu.set_key(0x9, false);
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc, 0x202);
+        assert_eq!(cpu.regs[4], 0x9);
+        assert_eq!(cpu.key_wait(), None);
+    }
+}
+
+#[test]
+fn ld_fx0a_counts_key_pressed_again() {
+    for quirks in ALL_QUIRKS {
+        let mut cpu = CpuTest::new(0xF40A).quirks(quirks).build();
+        cpu.set_key(0x2, true);
+        cpu.step().unwrap();
+        cpu.set_key(0x2, false);
+        cpu.step().unwrap();
+        cpu.set_key(0x2, true);
+        cpu.step().unwrap();
+        assert_eq!(cpu.regs[4], 0x2);
+        cpu.set_key(0x2, false);
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc, 0x202);
+    }
+}
+
+#[test]
+fn ld_fx15() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0xF415).quirks(quirks).reg(4, 0x3C).step();
+        assert_eq!(cpu.reg_delay, 0x3C);
+        assert_eq!(cpu.pc, 0x202);
+    }
+}
+
+#[test]
+fn ld_fx18() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0xF418).quirks(quirks).reg(4, 0x3C).step();
+        assert_eq!(cpu.reg_sound, 0x3C);
+        assert_eq!(cpu.pc, 0x202);
+    }
+}
+
+#[test]
+fn audio_f002() {
+    let pattern: Vec<u8> = (0..16).collect();
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0xF002)
+            .quirks(quirks)
+            .i(0x300)
+            .mem(0x300, &pattern)
+            .step();
+        assert_eq!(cpu.audio_pattern.unwrap()[..], pattern[..]);
+        assert_eq!(cpu.reg_i, 0x300);
+        assert_eq!(cpu.pc, 0x202);
+    }
+}
+
+#[test]
+fn ld_fx3a() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0xF43A).quirks(quirks).reg(4, 0x70).step();
+        assert_eq!(cpu.pitch, 0x70);
+        assert_eq!(cpu.pc, 0x202);
+    }
+}
+
+#[test]
+fn add_fx1e() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0xF41E)
+            .quirks(quirks)
+            .reg(4, 0x10)
+            .reg(0xF, 0x55)
+            .i(0xFF8)
+            .step();
+        assert_eq!(cpu.reg_i, 0x1008);
+        assert_eq!(cpu.regs[0xF], 0x55);
+        assert_eq!(cpu.pc, 0x202);
+    }
+}
+
+#[test]
+fn ld_fx29() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0xF429).quirks(quirks).reg(4, 0xA).step();
+        assert_eq!(cpu.reg_i, 50);
+        assert_eq!(&cpu.mem[50..55], &[0xF0, 0x90, 0xF0, 0x90, 0x90]);
+        assert_eq!(cpu.pc, 0x202);
+    }
+}
+
+#[test]
+fn ld_fx33() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0xF433)
+            .quirks(quirks)
+            .reg(4, 254)
+            .i(0x300)
+            .step();
+        assert_eq!(&cpu.mem[0x300..0x303], &[2, 5, 4]);
+        assert_eq!(cpu.reg_i, 0x300);
+        assert_eq!(cpu.pc, 0x202);
+    }
+}
+
+#[test]
+fn ld_fx55() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0xF255)
+            .quirks(quirks)
+            .reg(0, 1)
+            .reg(1, 2)
+            .reg(2, 3)
+            .reg(3, 4)
+            .i(0x300)
+            .step();
+        assert_eq!(&cpu.mem[0x300..0x304], &[1, 2, 3, 0]);
+        if quirks.st_compat {
+            assert_eq!(cpu.reg_i, 0x300);
+        } else {
+            assert_eq!(cpu.reg_i, 0x303);
+        }
+        assert_eq!(cpu.pc, 0x202);
+    }
+}
+
+#[test]
+fn ld_fx65() {
+    for quirks in ALL_QUIRKS {
+        let cpu = CpuTest::new(0xF265)
+            .quirks(quirks)
+            .reg(3, 0x99)
+            .i(0x300)
+            .mem(0x300, &[1, 2, 3, 4])
+            .step();
+        assert_eq!(&cpu.regs[0..4], &[1, 2, 3, 0x99]);
+        if quirks.st_compat {
+            assert_eq!(cpu.reg_i, 0x300);
+        } else {
+            assert_eq!(cpu.reg_i, 0x303);
+        }
+        assert_eq!(cpu.pc, 0x202);
+    }
+}
+
+#[test]
+fn timers_saturate() {
+    let mut cpu = CpuTest::new(0x0000).delay(1).sound(2).build();
+    cpu.dec_regs();
+    assert_eq!((cpu.reg_delay, cpu.reg_sound), (0, 1));
+    cpu.dec_regs();
+    assert_eq!((cpu.reg_delay, cpu.reg_sound), (0, 0));
+}
+
+#[test]
+fn ret_with_empty_stack() {
+    let mut cpu = CpuTest::new(0x00EE).build();
+    assert_eq!(cpu.step(), Err(CpuError::StackUnderflow));
+}
+
+#[test]
+fn call_stack_overflow() {
+    // Calls itself forever
+    let mut cpu = CpuTest::new(0x2200).build();
+    for _ in 0..16 {
+        cpu.step().unwrap();
+    }
+    assert_eq!(cpu.step(), Err(CpuError::StackOverflow));
+    assert_eq!(cpu.stack.len(), 16);
+}
+
+#[test]
+fn unknown_instruction() {
+    let mut cpu = CpuTest::new(0x5121).build();
+    assert_eq!(cpu.step(), Err(CpuError::UnknownInstruction(0x5121)));
+    assert_eq!(cpu.pc, 0x200);
+}
+
+#[test]
+fn pc_out_of_bounds() {
+    let mut cpu = CpuTest::new(0xBFFF).reg(0, 0xFF).build();
+    cpu.step().unwrap();
+    assert_eq!(cpu.step(), Err(CpuError::PcOutOfBounds(0x10FE)));
+}
+
+#[test]
+fn mem_out_of_bounds() {
+    for opcode in [0xD005, 0xF002, 0xF033, 0xF355, 0xF365] {
+        let mut cpu = CpuTest::new(opcode).i(0xFFE).build();
+        assert!(matches!(cpu.step(), Err(CpuError::MemOutOfBounds(_))));
+    }
+}
+
+#[test]
+fn skp_masks_key_index() {
+    let cpu = CpuTest::new(0xE39E).reg(3, 0xFB).key(0xB).step();
+    assert_eq!(cpu.pc, 0x204);
+}
+
+#[test]
+fn rom_too_large() {
+    let mut cpu = Cpu::new();
+    assert_eq!(cpu.init_mem(&[0; 0xE00]), Ok(()));
+    assert_eq!(cpu.init_mem(&[0; 0xE01]), Err(CpuError::RomTooLarge(0xE01)));
+}
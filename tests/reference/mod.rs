@@ -0,0 +1,261 @@
+//! A deliberately simple CHIP-8 interpreter, written independently of `wasm_chip8::cpu` so the
+//! two can be run side by side. It favours obviousness over speed: the display is one `u64` per
+//! row and everything is decoded from the raw 16-bit opcode.
+
+const FONT: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+#[derive(Debug, PartialEq)]
+pub enum Fault {
+    Halted(u16),
+    PcOutOfBounds,
+}
+
+pub struct Reference {
+    pub ram: Vec<u8>,
+    pub v: [u8; 16],
+    pub i: usize,
+    pub pc: usize,
+    /// Return addresses, i.e. the instruction after each CALL
+    pub stack: Vec<usize>,
+    pub dt: u8,
+    pub st: u8,
+    pub display: [u64; 32],
+    pub keys: u16,
+    /// Keys already down when FX0A started waiting, until they're released
+    getkey_held: Option<u16>,
+    /// The key FX0A stored, until it's released
+    getkey_latched: Option<u8>,
+
+    /// FX55/FX65 leave I untouched
+    pub keep_i: bool,
+    /// 8XY6/8XYE shift VX instead of VY
+    pub shift_vx: bool,
+}
+
+impl Reference {
+    pub fn new(rom: &[u8]) -> Self {
+        let mut ram = vec![0; 4096];
+        ram[..FONT.len()].copy_from_slice(&FONT);
+        ram[0x200..0x200 + rom.len()].copy_from_slice(rom);
+
+        Self {
+            ram,
+            v: [0; 16],
+            i: 0,
+            pc: 0x200,
+            stack: Vec::new(),
+            dt: 0,
+            st: 0,
+            display: [0; 32],
+            keys: 0,
+            getkey_held: None,
+            getkey_latched: None,
+            keep_i: false,
+            shift_vx: false,
+        }
+    }
+
+    pub fn opcode(&self) -> u16 {
+        u16::from(self.ram[self.pc]) << 8 | u16::from(self.ram[self.pc + 1])
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.display[y] & (1 << (63 - x)) != 0
+    }
+
+    pub fn tick_timers(&mut self) {
+        self.dt = self.dt.saturating_sub(1);
+        self.st = self.st.saturating_sub(1);
+    }
+
+    pub fn step(&mut self) -> Result<(), Fault> {
+        if self.pc + 1 >= self.ram.len() {
+            return Err(Fault::PcOutOfBounds);
+        }
+
+        let op = self.opcode();
+        let x = usize::from(op >> 8 & 0xF);
+        let y = usize::from(op >> 4 & 0xF);
+        let n = usize::from(op & 0xF);
+        let kk = (op & 0xFF) as u8;
+        let nnn = usize::from(op & 0xFFF);
+
+        let mut next = self.pc + 2;
+
+        match op >> 12 {
+            0x0 => match op {
+                0x00E0 => self.display = [0; 32],
+                0x00EE => next = self.stack.pop().ok_or(Fault::Halted(op))?,
+                0x00FA => self.keep_i = true,
+                // SYS calls are ignored and spin in place
+                _ => next = self.pc,
+            },
+            0x1 => next = nnn,
+            0x2 => {
+                if self.stack.len() == 16 {
+                    return Err(Fault::Halted(op));
+                }
+                self.stack.push(self.pc + 2);
+                next = nnn;
+            }
+            0x3 if self.v[x] == kk => next += 2,
+            0x3 => {}
+            0x4 if self.v[x] != kk => next += 2,
+            0x4 => {}
+            0x5 if n == 0 => {
+                if self.v[x] == self.v[y] {
+                    next += 2;
+                }
+            }
+            0x6 => self.v[x] = kk,
+            0x7 => self.v[x] = self.v[x].wrapping_add(kk),
+            0x8 => {
+                let (vx, vy) = (self.v[x], self.v[y]);
+                let (result, flag) = match n {
+                    0x0 => (vy, None),
+                    0x1 => (vx | vy, None),
+                    0x2 => (vx & vy, None),
+                    0x3 => (vx ^ vy, None),
+                    0x4 => (
+                        vx.wrapping_add(vy),
+                        Some((u16::from(vx) + u16::from(vy) > 255) as u8),
+                    ),
+                    0x5 => (vx.wrapping_sub(vy), Some((vx >= vy) as u8)),
+                    0x7 => (vy.wrapping_sub(vx), Some((vy >= vx) as u8)),
+                    0x6 => {
+                        let src = if self.shift_vx { vx } else { vy };
+                        (src >> 1, Some(src & 1))
+                    }
+                    0xE => {
+                        let src = if self.shift_vx { vx } else { vy };
+                        (src << 1, Some(src >> 7))
+                    }
+                    _ => return Err(Fault::Halted(op)),
+                };
+                self.v[x] = result;
+                if let Some(flag) = flag {
+                    self.v[0xF] = flag;
+                }
+            }
+            0x9 if n == 0 => {
+                if self.v[x] != self.v[y] {
+                    next += 2;
+                }
+            }
+            0xA => self.i = nnn,
+            0xB => next = nnn + usize::from(self.v[0]),
+            // The caller fills in the random byte, see `differential.rs`
+            0xC => self.v[x] = 0,
+            0xD => {
+                let (left, top) = (usize::from(self.v[x]), usize::from(self.v[y]));
+                if self.i + n > self.ram.len() {
+                    return Err(Fault::Halted(op));
+                }
+                let mut collision = false;
+                for row in 0..n {
+                    let sprite = self.ram[self.i + row];
+                    let line = (top + row) % 32;
+                    for col in 0..8 {
+                        if sprite & (0x80 >> col) == 0 {
+                            continue;
+                        }
+                        let bit = 1u64 << (63 - (left + col) % 64);
+                        collision |= self.display[line] & bit != 0;
+                        self.display[line] ^= bit;
+                    }
+                }
+                self.v[0xF] = collision as u8;
+            }
+            0xE => {
+                let down = self.keys & (1 << (self.v[x] & 0xF)) != 0;
+                match kk {
+                    0x9E if down => next += 2,
+                    0xA1 if !down => next += 2,
+                    0x9E | 0xA1 => {}
+                    _ => return Err(Fault::Halted(op)),
+                }
+            }
+            0xF => match kk {
+                // XO-CHIP audio only affects what is heard, there's nothing to compare
+                0x02 if x == 0 => {
+                    if self.i + 16 > self.ram.len() {
+                        return Err(Fault::Halted(op));
+                    }
+                }
+                0x3A => {}
+                0x07 => self.v[x] = self.dt,
+                0x0A => {
+                    // Latch the first key pressed after the wait started, continue once that one
+                    // is released
+                    next = self.pc;
+                    if let Some(key) = self.getkey_latched {
+                        if self.keys & (1 << key) == 0 {
+                            self.getkey_latched = None;
+                            next = self.pc + 2;
+                        }
+                    } else {
+                        let held = self.getkey_held.get_or_insert(self.keys);
+                        *held &= self.keys;
+                        let pressed = self.keys & !*held;
+                        if pressed != 0 {
+                            let key = pressed.trailing_zeros() as u8;
+                            self.v[x] = key;
+                            self.getkey_latched = Some(key);
+                            self.getkey_held = None;
+                        }
+                    }
+                }
+                0x15 => self.dt = self.v[x],
+                0x18 => self.st = self.v[x],
+                0x1E => self.i += usize::from(self.v[x]),
+                0x29 => self.i = usize::from(self.v[x]) * 5,
+                0x33 => {
+                    if self.i + 3 > self.ram.len() {
+                        return Err(Fault::Halted(op));
+                    }
+                    let vx = self.v[x];
+                    self.ram[self.i] = vx / 100;
+                    self.ram[self.i + 1] = vx / 10 % 10;
+                    self.ram[self.i + 2] = vx % 10;
+                }
+                0x55 | 0x65 => {
+                    if self.i + x + 1 > self.ram.len() {
+                        return Err(Fault::Halted(op));
+                    }
+                    for reg in 0..=x {
+                        if kk == 0x55 {
+                            self.ram[self.i + reg] = self.v[reg];
+                        } else {
+                            self.v[reg] = self.ram[self.i + reg];
+                        }
+                    }
+                    if !self.keep_i {
+                        self.i += x + 1;
+                    }
+                }
+                _ => return Err(Fault::Halted(op)),
+            },
+            _ => return Err(Fault::Halted(op)),
+        }
+
+        self.pc = next;
+        Ok(())
+    }
+}
@@ -0,0 +1,178 @@
+use wasm_chip8::cpu::{Cpu, VIP_CYCLES_PER_FRAME};
+use wasm_chip8::scheduler::Scheduler;
+
+// ADD I, V1; JP 0x200. With V1 = 1, I counts every second instruction.
+const COUNTER: [u8; 4] = [0xF1, 0x1E, 0x12, 0x00];
+
+fn counter() -> Cpu {
+    let mut cpu = Cpu::new();
+    cpu.init_mem(&COUNTER).unwrap();
+    cpu.regs[1] = 1;
+    cpu.reg_delay = 255;
+    cpu
+}
+
+/// Runs for a bit over a second at `fps`, returning the timer ticks.
+fn run_second(scheduler: &mut Scheduler, cpu: &mut Cpu, fps: u32) -> usize {
+    let mut ticks = 0;
+    for _ in 0..fps {
+        ticks += scheduler.update(cpu, 1.0 / f64::from(fps), 1.0).unwrap();
+    }
+    // Float sums of frame times can land just short of the last tick
+    ticks + scheduler.update(cpu, 0.5 / 60.0, 1.0).unwrap()
+}
+
+#[test]
+fn speed_independent_of_frame_rate() {
+    for fps in &[30, 60, 75, 144, 240] {
+        let mut cpu = counter();
+        let mut scheduler = Scheduler::new(480);
+
+        assert_eq!(
+            run_second(&mut scheduler, &mut cpu, *fps),
+            60,
+            "{} fps",
+            fps
+        );
+        assert_eq!(cpu.reg_i, 240, "{} fps", fps);
+        assert_eq!(cpu.reg_delay, 195, "{} fps", fps);
+    }
+}
+
+#[test]
+fn configurable_ips() {
+    for ips in &[60, 100, 700, 1000] {
+        let mut cpu = counter();
+        let mut scheduler = Scheduler::new(*ips);
+
+        run_second(&mut scheduler, &mut cpu, 60);
+        assert_eq!(cpu.reg_i as u32, ips / 2, "{} ips", ips);
+        assert_eq!(cpu.reg_delay, 195, "{} ips", ips);
+    }
+}
+
+#[test]
+fn long_pauses_are_not_caught_up() {
+    let mut cpu = counter();
+    let mut scheduler = Scheduler::new(480);
+
+    assert!(scheduler.update(&mut cpu, 10.0, 1.0).unwrap() <= 15);
+}
+
+#[test]
+fn speed_scales_past_the_catch_up_cap() {
+    let mut cpu = counter();
+    let mut scheduler = Scheduler::new(480);
+
+    // At 4fps, a 16x turbo frame covers four seconds
+    let ticks = scheduler.update(&mut cpu, 0.25, 16.0).unwrap();
+    assert!((239..=240).contains(&ticks), "{} ticks", ticks);
+    // Slow motion isn't capped any differently
+    assert!(scheduler.update(&mut cpu, 10.0, 0.5).unwrap() <= 8);
+}
+
+#[test]
+fn vip_timing_budgets_cycles() {
+    let mut cpu = counter();
+    cpu.vip_timing = true;
+    let mut scheduler = Scheduler::new(480);
+
+    // Each ADD I + JP pair costs 19 + 23 cycles, the IPS setting is ignored
+    scheduler.tick(&mut cpu).unwrap();
+    let pairs = VIP_CYCLES_PER_FRAME.div_ceil(42);
+    assert!(cpu.reg_i as u32 >= pairs - 1 && cpu.reg_i as u32 <= pairs);
+    assert!(cpu.cycles >= u64::from(VIP_CYCLES_PER_FRAME));
+}
+
+#[test]
+fn vip_timing_carries_overrun() {
+    // A 15 row sprite costs more than a third of a frame, so only 2 or 3 fit in each
+    let mut cpu = Cpu::new();
+    cpu.init_mem(&[0xD0, 0x0F, 0x12, 0x00]).unwrap();
+    cpu.vip_timing = true;
+    let mut scheduler = Scheduler::default();
+
+    for _ in 0..60 {
+        scheduler.tick(&mut cpu).unwrap();
+    }
+    let frames = 60 * u64::from(VIP_CYCLES_PER_FRAME);
+    assert!(cpu.cycles >= frames && cpu.cycles < frames + 1650 + 23);
+}
+
+#[test]
+fn cycles_only_count_with_vip_timing() {
+    let mut cpu = counter();
+    Scheduler::new(480).tick(&mut cpu).unwrap();
+    assert!(cpu.reg_i > 0);
+    assert_eq!(cpu.cycles, 0);
+}
+
+#[test]
+fn stalled_draw_is_charged_once() {
+    // DRW V0, V0, 1; JP 0x200
+    let mut cpu = Cpu::new();
+    cpu.init_mem(&[0xD0, 0x01, 0x12, 0x00]).unwrap();
+    cpu.vip_timing = true;
+    cpu.display_wait = true;
+
+    cpu.step().unwrap();
+    assert!(cpu.waiting_for_vblank());
+    assert_eq!(cpu.cycles, 0);
+    cpu.vblank();
+    cpu.step().unwrap();
+    assert_eq!(cpu.pc, 0x202);
+    assert_eq!(cpu.cycles, 250);
+}
+
+#[test]
+fn display_wait_draws_once_per_frame() {
+    // DRW V0, V0, 1; JP 0x200
+    let mut cpu = Cpu::new();
+    cpu.init_mem(&[0xD0, 0x01, 0x12, 0x00]).unwrap();
+    cpu.display_wait = true;
+    let mut scheduler = Scheduler::new(1000);
+
+    for frame in 1..=10 {
+        scheduler.tick(&mut cpu).unwrap();
+        // The sprite toggles with every draw, the first one only happens after a frame boundary
+        assert_eq!(cpu.framebuffer[0], frame % 2 == 0, "frame {}", frame);
+    }
+}
+
+#[test]
+fn previous_frame_is_one_tick_back() {
+    // DRW V0, V0, 1; JP 0x200, toggling the sprite once per frame
+    let mut cpu = Cpu::new();
+    cpu.init_mem(&[0xD0, 0x01, 0x12, 0x00]).unwrap();
+    cpu.display_wait = true;
+    let mut scheduler = Scheduler::new(1000);
+
+    // Several ticks in one update, like in turbo, still only look one frame back
+    assert_eq!(scheduler.update(&mut cpu, 0.1, 1.0).unwrap(), 6);
+    assert!(cpu.framebuffer[0]);
+    assert!(!scheduler.previous_frame()[0]);
+
+    // Stepping within a frame doesn't move it on
+    cpu.step().unwrap();
+    assert!(!cpu.framebuffer[0]);
+    assert!(!scheduler.previous_frame()[0]);
+    scheduler.tick(&mut cpu).unwrap();
+    assert!(scheduler.previous_frame()[0]);
+}
+
+#[test]
+fn records_frames() {
+    let mut cpu = counter();
+    let mut scheduler = Scheduler::default();
+    scheduler.tick(&mut cpu).unwrap();
+    assert!(scheduler.take_frames().is_empty());
+
+    scheduler.record_frames = true;
+    for _ in 0..3 {
+        scheduler.tick(&mut cpu).unwrap();
+    }
+    let frames = scheduler.take_frames();
+    assert_eq!(frames.len(), 3);
+    assert_eq!(frames[0], *cpu.get_framebuffer());
+    assert!(scheduler.take_frames().is_empty());
+}
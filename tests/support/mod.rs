@@ -0,0 +1,132 @@
+// Shared between integration tests, not every test binary uses every helper
+#![allow(dead_code)]
+
+use wasm_chip8::cpu::Cpu;
+
+/// The compatibility flags of a `Cpu`.
+#[derive(Clone, Copy, Debug)]
+pub struct Quirks {
+    pub st_compat: bool,
+    pub sh_compat: bool,
+    pub vip_timing: bool,
+    pub display_wait: bool,
+}
+
+/// Every combination of the quirk flags.
+pub const ALL_QUIRKS: [Quirks; 16] = all_quirks();
+
+const fn all_quirks() -> [Quirks; 16] {
+    let mut all = [Quirks {
+        st_compat: false,
+        sh_compat: false,
+        vip_timing: false,
+        display_wait: false,
+    }; 16];
+    let mut bits = 0;
+    while bits < all.len() {
+        all[bits] = Quirks {
+            st_compat: bits & 1 != 0,
+            sh_compat: bits & 2 != 0,
+            vip_timing: bits & 4 != 0,
+            display_wait: bits & 8 != 0,
+        };
+        bits += 1;
+    }
+    all
+}
+
+/// Builds a `Cpu` around a single instruction so that it can be executed and inspected.
+pub struct CpuTest {
+    cpu: Cpu,
+    opcode: u16,
+}
+
+impl CpuTest {
+    pub fn new(opcode: u16) -> Self {
+        let mut cpu = Cpu::new();
+        cpu.init_mem(&[]).unwrap();
+
+        Self { cpu, opcode }
+    }
+
+    pub fn quirks(mut self, quirks: Quirks) -> Self {
+        self.cpu.st_compat = quirks.st_compat;
+        self.cpu.sh_compat = quirks.sh_compat;
+        self.cpu.vip_timing = quirks.vip_timing;
+        self.cpu.display_wait = quirks.display_wait;
+        self
+    }
+
+    pub fn pc(mut self, pc: usize) -> Self {
+        self.cpu.pc = pc;
+        self
+    }
+
+    pub fn reg(mut self, reg: usize, value: u8) -> Self {
+        self.cpu.regs[reg] = value;
+        self
+    }
+
+    pub fn i(mut self, value: usize) -> Self {
+        self.cpu.reg_i = value;
+        self
+    }
+
+    pub fn delay(mut self, value: u8) -> Self {
+        self.cpu.reg_delay = value;
+        self
+    }
+
+    pub fn sound(mut self, value: u8) -> Self {
+        self.cpu.reg_sound = value;
+        self
+    }
+
+    pub fn stack(mut self, stack: &[usize]) -> Self {
+        self.cpu.stack = stack.to_vec();
+        self
+    }
+
+    pub fn mem(mut self, addr: usize, bytes: &[u8]) -> Self {
+        self.cpu.mem[addr..addr + bytes.len()].copy_from_slice(bytes);
+        self
+    }
+
+    pub fn key(mut self, key: usize) -> Self {
+        self.cpu.set_key(key, true);
+        self
+    }
+
+    pub fn pixel(mut self, x: usize, y: usize) -> Self {
+        self.cpu.framebuffer[y * 64 + x] = true;
+        self
+    }
+
+    /// Places the instruction at the current PC without executing it.
+    pub fn build(mut self) -> Cpu {
+        let [high, low] = self.opcode.to_be_bytes();
+        self.cpu.mem[self.cpu.pc] = high;
+        self.cpu.mem[self.cpu.pc + 1] = low;
+        self.cpu
+    }
+
+    /// Places the instruction at the current PC and executes it, carrying on past the frame
+    /// boundary it may wait for with `display_wait`.
+    pub fn step(self) -> Cpu {
+        let mut cpu = self.build();
+        cpu.step().unwrap();
+        if cpu.waiting_for_vblank() {
+            cpu.vblank();
+            cpu.step().unwrap();
+        }
+        cpu
+    }
+}
+
+pub fn lit(cpu: &Cpu, x: usize, y: usize) -> bool {
+    cpu.get_framebuffer()[y * 64 + x]
+}
+
+pub fn lit_count(cpu: &Cpu) -> usize {
+    cpu.get_framebuffer().iter().filter(|e| **e).count()
+}